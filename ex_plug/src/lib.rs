@@ -1,20 +1,9 @@
-#[derive(Clone, Copy)]
-pub struct IComplex {
-    pub len: i64,
-    pub angle_deg: i64
-}
-
-#[derive(Clone, Copy)]
-pub struct FComplex {
-    pub len: f64,
-    pub angle: f64
-}
-
+#[repr(C)]
 #[derive(Clone)]
-pub struct Var {
-    pub ls_data: Option<Vec<Var>>,
-    pub num_data: Option<FComplex>,
-    pub int_data: Option<IComplex>
+pub enum Var {
+    Int { re: i64, im: i64 },
+    Float { re: f64, im: f64 },
+    List(Vec<Var>)
 }
 
 #[no_mangle]
@@ -25,4 +14,3 @@ pub fn execute(vars: &Vec<Var>) -> Result<Var, String> {
         Ok(vars[0].clone())
     }
 }
-
@@ -15,7 +15,30 @@ pub fn cli_args() -> ArgMatches {
         .arg(
             Arg::new("stmts")
                 .takes_value(true)
-                .help("Immediately evaluate '\\n' separated lines\n(leave blank for REPL)")
+                .help("Immediately evaluate '\\n' separated lines, '-' for stdin, or a script file (ending in .calc, or a path like ./script)\n(leave blank for REPL, or to read stdin when it isn't a terminal)")
+        ).arg(
+            Arg::new("file")
+                .short('f')
+                .long("file")
+                .takes_value(true)
+                .conflicts_with("stmts")
+                .help("Run a script file, stopping at the first error")
+        ).arg(
+            Arg::new("keep-going")
+                .short('k')
+                .long("keep-going")
+                .help("Keep running a script after an error (still exits with failure)")
+        ).arg(
+            Arg::new("set")
+                .short('s')
+                .long("set")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .value_name("name=value")
+                .help("Change a setting like ':set' does, after the init file runs")
+        ).arg(
+            Arg::new("json")
+                .long("json")
+                .help("Print one JSON object per statement instead of text")
         ).get_matches()
 }
-
@@ -4,15 +4,31 @@
  */
 
 use std::f64::consts;
+use num_traits::{
+    Signed, Zero
+};
 use crate::{
-    var::Var,
-    complex::{
+    var::{
+        Var, ratio_to_f64
+    },
+    error::CalcError,
+    int::{
+        Int, MAX_RESULT_BITS
+    }, decimal::Decimal,
+    settings::{
+        Settings, FloatMode
+    }, complex::{
         FComplex,
         IComplex
+    }, word::{
+        Word, MAX_BITS
     }
 };
 
-pub const BUILTIN_FUNCS: [(&str, &fn(&Vec<Var>)->Result<Var, String>); 22] = [
+// Builtins get the session settings so things like pi() know which kind of float to give back
+pub type Builtin = fn(&Vec<Var>, &Settings)->Result<Var, CalcError>;
+
+pub const BUILTIN_FUNCS: [(&str, &Builtin); 28] = [
     ("sin", &SIN),
     ("cos", &COS),
     ("tan", &TAN),
@@ -34,173 +50,629 @@ pub const BUILTIN_FUNCS: [(&str, &fn(&Vec<Var>)->Result<Var, String>); 22] = [
     ("app", &APP),
     ("del", &DEL),
     ("sign", &SIGN),
-    ("comp", &COMP)
+    ("comp", &COMP),
+    ("fact", &FACT),
+    ("choose", &CHOOSE),
+    ("div", &DIV),
+    ("float", &FLOAT),
+    ("sext", &SEXT),
+    ("trunc", &TRUNC)
 ];
 
-pub const SIN: fn(&Vec<Var>)->Result<Var, String> = |vars| {
+// How to call each builtin and what it does, for ':help'
+pub const BUILTIN_HELP: [(&str, &str, &str); 33] = [
+    ("call", "call(lib, args...)", "call the plugin lib (from .config/calc/plugins) with args"),
+    ("sin", "sin(x)", "sine of x in radians"),
+    ("cos", "cos(x)", "cosine of x in radians"),
+    ("tan", "tan(x)", "tangent of x in radians"),
+    ("asin", "asin(x)", "arcsine, complex outside of [-1, 1]"),
+    ("acos", "acos(x)", "arccosine, complex outside of [-1, 1]"),
+    ("atan", "atan(x)", "arctangent"),
+    ("d2r", "d2r(x)", "degrees to radians"),
+    ("r2d", "r2d(x)", "radians to degrees"),
+    ("log", "log(x)", "log base 10 of x"),
+    ("ln", "ln(x)", "log base e of x"),
+    ("e", "e()", "Euler's number"),
+    ("pi", "pi()", "pi"),
+    ("mod", "mod(x, y)", "x - y*floor(x/y), exact for ints and fractions (complex ints round x/y to the nearest)"),
+    ("floor", "floor(x)", "round down"),
+    ("ceil", "ceil(x)", "round up"),
+    ("abs", "abs(x)", "absolute value, i.e. |x|"),
+    ("idx", "idx(ls, n)", "nth item in ls, counting from 0 (negative n counts from the end)"),
+    ("len", "len(ls)", "number of items in ls"),
+    ("app", "app(ls, x)", "ls with x added to the end"),
+    ("del", "del(ls, n)", "ls without its nth item"),
+    ("sign", "sign(x)", "-1, 0 or 1 for real x, and x/|x| for complex x"),
+    ("comp", "comp(r, ang)", "complex number from a radius and angle"),
+    ("fact", "fact(n)", "n! for ints n >= 0"),
+    ("choose", "choose(n, k)", "n choose k for ints n, k >= 0"),
+    ("div", "div(x, y)", "floor division of two ints (nearest for complex ints)"),
+    ("float", "float(x)", "convert an int or fraction to a float (decimal in decimal mode)"),
+    ("sext", "sext(x, n)", "sign extend the low n bits of int x, e.g. sext(0xFF_, 8_) is -1"),
+    ("trunc", "trunc(x, n)", "just the low n bits of int x, e.g. trunc(0x1FF_, 8_) is 255"),
+    ("hex", "hex(x)", "show the int result x in hex (has to be the whole expression)"),
+    ("oct", "oct(x)", "show the int result x in octal (has to be the whole expression)"),
+    ("bin", "bin(x)", "show the int result x in binary (has to be the whole expression)"),
+    ("base", "base(x, b)", "show the int result x in base b from 2 to 36 (has to be the whole expression)")
+];
+
+pub const SIN: Builtin = |vars, settings| map_unary("sin", vars, |var| float_fn(
+    "sin", var, settings, FComplex::sin, Decimal::sin
+));
+
+pub const COS: Builtin = |vars, settings| map_unary("cos", vars, |var| float_fn(
+    "cos", var, settings, FComplex::cos, Decimal::cos
+));
+
+pub const TAN: Builtin = |vars, settings| map_unary("tan", vars, |var| {
+    SIN(&vec![ var.clone() ], settings)? / COS(&vec![ var.clone() ], settings)?
+});
+
+// The inverse functions all use the principal branch, so e.g. asin(2) is complex instead of an error
+pub const ASIN: Builtin = |vars, settings| map_unary("asin", vars, |var| float_fn(
+    "asin", var, settings, FComplex::asin, Decimal::asin
+));
+
+pub const ACOS: Builtin = |vars, settings| map_unary("acos", vars, |var| float_fn(
+    "acos", var, settings, FComplex::acos, Decimal::acos
+));
+
+pub const ATAN: Builtin = |vars, settings| map_unary("atan", vars, |var| float_fn(
+    "atan", var, settings, FComplex::atan, Decimal::atan
+));
+
+pub const D2R: Builtin = |vars, settings| map_unary("d2r", vars, |var| {
+    (var.clone() * PI(&vec![], settings)?)? / Var::Int(IComplex::from_real(180))
+});
+
+pub const R2D: Builtin = |vars, settings| map_unary("r2d", vars, |var| {
+    (var.clone() * Var::Int(IComplex::from_real(180)))? / PI(&vec![], settings)?
+});
+
+pub const LOG: Builtin = |vars, settings| map_unary("log", vars, |var| float_fn(
+    "log", var, settings, FComplex::log10, Decimal::log10
+));
+
+pub const LN: Builtin = |vars, settings| map_unary("ln", vars, |var| float_fn(
+    "ln", var, settings, FComplex::ln, Decimal::ln
+));
+
+pub const LEN: Builtin = |vars, _| {
     if vars.len() != 1 {
-        Err(String::from("Expected one argument for sin."))
-    } else if vars[0].ls_data.is_some() {
-        let mut results = Vec::new();
-        for var in vars[0].clone().ls_data.unwrap() {
-            match SIN(&vec![ var.clone() ]) {
-                Err(err) => return Err(err),
-                Ok(val) => results.push(val)
-            }
-        }
-        Ok(Var {
-            ls_data: Some(results),
-            num_data: None,
-            int_data: None
-        })
-    } else {
-        // Only floats. It's sin
-        let (a, b) = vars[0].to_float().num_data.unwrap().to_cardinal();
-
-        /*
-        * How do we do this generally?
-        * sin(x) = 0.5e^jx - 0.5e^-jx, so what if x is complex?
-        *
-        * sin(a + jb) = 0.5*e^j(a + jb) - 0.5*e^-j(a + jb)
-        * = 0.5*(e^ja)*e^-b - 0.5*(e^-ja)*(e^b)
-        * = 0.5*e^-b angle(a) - 0.5*e^b angle(-a)
-        *
-        * Note: It's only the imaginary part
-        */
-
-        let len1 = 0.5 * (-b).exp();
-        let angle1 = a;
-        let len2 = 0.5 * b.exp();
-        let angle2 = -a;
-        
-        let val1 = FComplex::new_polar(len1, angle1);
-        let val2 = FComplex::new_polar(len2, angle2);
-
-        let var_data = val1 - val2;
-        let (_, var_data_b) = var_data.to_cardinal();
-        let var_data = FComplex::new_cardinal(var_data_b, 0.0); // Im(answer)
-
-        Ok(Var {
-            ls_data: None,
-            num_data: Some(var_data),
-            int_data: None
-        })
+        return Err(CalcError::Arity { func: String::from("len"), expected: 1, found: vars.len() });
+    }
+
+    match &vars[0] {
+        Var::List(ls) => Ok(Var::Int(IComplex::from_real(ls.len() as i64))),
+        _ => Ok(Var::Int(IComplex::from_real(1)))
     }
 };
 
-// Like sine, but e^x + e^x instead of -
-pub const COS: fn(&Vec<Var>)->Result<Var, String> = |vars| {
-    if vars.len() != 1 {
-        Err(String::from("Expected one argument for sin."))
-    } else if vars[0].ls_data.is_some() {
-        let mut results = Vec::new();
-        for var in vars[0].clone().ls_data.unwrap() {
-            match SIN(&vec![ var.clone() ]) {
-                Err(err) => return Err(err),
-                Ok(val) => results.push(val)
-            }
-        }
-        Ok(Var {
-            ls_data: Some(results),
-            num_data: None,
-            int_data: None
-        })
+pub const E: Builtin = |vars, settings| {
+    if !vars.is_empty() {
+        Err(CalcError::Arity { func: String::from("e"), expected: 0, found: vars.len() })
+    } else if settings.float_mode == FloatMode::Decimal {
+        Ok(Var::Decimal(Decimal::e(settings.precision)))
     } else {
-        // Only floats. It's sin
-        let (a, b) = vars[0].to_float().num_data.unwrap().to_cardinal();
-
-        let len1 = 0.5 * (-b).exp();
-        let angle1 = a;
-        let len2 = 0.5 * b.exp();
-        let angle2 = -a;
-        
-        let val1 = FComplex::new_polar(len1, angle1);
-        let val2 = FComplex::new_polar(len2, angle2);
-
-        let var_data = val1 + val2;
-        let (var_data_a, _) = var_data.to_cardinal();
-        let var_data = FComplex::new_cardinal(var_data_a, 0.0); // Re(answer)
-
-        Ok(Var {
-            ls_data: None,
-            num_data: Some(var_data),
-            int_data: None
-        })
+        Ok(Var::Float(FComplex::new_polar(consts::E, 0.0)))
     }
 };
 
-pub const TAN: fn(&Vec<Var>)->Result<Var, String> = |vars| {
-    if vars.len() != 1 {
-        Err(String::from("Expected one argument for tan."))
+pub const PI: Builtin = |vars, settings| {
+    if !vars.is_empty() {
+        Err(CalcError::Arity { func: String::from("pi"), expected: 0, found: vars.len() })
+    } else if settings.float_mode == FloatMode::Decimal {
+        Ok(Var::Decimal(Decimal::pi(settings.precision)))
     } else {
-        match SIN(vars) {
-            Err(err) => Err(err),
-            Ok(sin_val) => {
-                match COS(vars) {
-                    Err(err) => Err(err),
-                    Ok(cos_val) => Ok(sin_val / cos_val)
-                }
-            }
+        Ok(Var::Float(FComplex::new_polar(consts::PI, 0.0)))
+    }
+};
+
+// Takes the sign of y like floor division does, i.e. x - y*floor(x/y), and stays exact for ints
+pub const MOD: Builtin = |vars, _| {
+    if vars.len() != 2 {
+        return Err(CalcError::Arity { func: String::from("mod"), expected: 2, found: vars.len() });
+    }
+
+    vars[0].clone().do_op(
+        vars[1].clone(),
+        |a, b| if b == FComplex::zero() { Err(CalcError::DivisionByZero) } else { Ok(a - b * (a / b).floor()) },
+        |a, b| if b == IComplex::zero() { Err(CalcError::DivisionByZero) } else { Ok(Var::Int(a % b)) },
+        |a, b| if b.is_zero() {
+            Err(CalcError::DivisionByZero)
+        } else {
+            Ok(Var::from_ratio(a.clone() - b.clone() * (a / b).floor()))
+        }, |a, b| if b.is_zero() {
+            Err(CalcError::DivisionByZero)
+        } else {
+            Ok(Var::Decimal(a.clone() - (b.clone() * (a / b)?.floor())?))
         }
+    )
+};
+
+// Complex numbers get the real and imaginary parts rounded separately
+pub const FLOOR: Builtin = |vars, _| map_unary("floor", vars, |var| Ok(match var {
+    Var::Rational(ratio) => Var::from_ratio(ratio.floor()),
+    Var::Float(num) => Var::Float(num.floor()),
+    Var::Decimal(num) => Var::Decimal(num.floor()),
+    var => var.clone()
+}));
+
+pub const CEIL: Builtin = |vars, _| map_unary("ceil", vars, |var| Ok(match var {
+    Var::Rational(ratio) => Var::from_ratio(ratio.ceil()),
+    Var::Float(num) => Var::Float(num.ceil()),
+    Var::Decimal(num) => Var::Decimal(num.ceil()),
+    var => var.clone()
+}));
+
+// Complex ints only stay ints when |z| comes out whole, e.g. abs(3_ + j4_) = 5
+pub const ABS: Builtin = |vars, _| map_unary("abs", vars, |var| Ok(match var {
+    Var::Int(int) if int.is_real() => Var::Int(IComplex::new_cardinal(
+        if int.re.is_negative() { -int.re.clone() } else { int.re.clone() }, Int::from(0)
+    )), Var::Int(int) => {
+        let norm = int.norm().to_big();
+        let root = norm.sqrt();
+        if &root * &root == norm {
+            Var::Int(IComplex::new_cardinal(Int::from_big(root), Int::from(0)))
+        } else {
+            Var::Float(FComplex::new_cardinal(int.to_fcomplex().len(), 0.0))
+        }
+    }, Var::Rational(ratio) => Var::Rational(ratio.abs()),
+    Var::Float(num) => Var::Float(FComplex::new_cardinal(num.len(), 0.0)),
+    Var::Decimal(num) => Var::Decimal(num.abs()),
+    Var::List(_) => unreachable!("Lists are mapped over")
+}));
+
+// Lists count from 0, and negative indices count back from the end
+pub const IDX: Builtin = |vars, _| {
+    if vars.len() != 2 {
+        return Err(CalcError::Arity { func: String::from("idx"), expected: 2, found: vars.len() });
     }
+
+    let ls = list_arg("idx", &vars[0])?;
+    Ok(ls[list_index("idx", ls.len(), &vars[1])?].clone())
 };
 
-pub const LEN: fn(&Vec<Var>)->Result<Var, String> = |vars| {
-    if vars.len() != 1 {
-        Err(String::from("Expected on argument for len."))
-    } else if vars[0].ls_data.is_some() {
-        Ok(Var {
-            ls_data: None,
-            num_data: None,
-            int_data: Some(IComplex::new_polar(vars[0].clone().ls_data.unwrap().len() as i64, 0))
-        })
-    } else {
-        Ok(Var {
-            ls_data: None,
-            num_data: None,
-            int_data: Some(IComplex::new_polar(1, 0))
-        })
+pub const APP: Builtin = |vars, _| {
+    if vars.len() != 2 {
+        return Err(CalcError::Arity { func: String::from("app"), expected: 2, found: vars.len() });
     }
+
+    let mut ls = list_arg("app", &vars[0])?.clone();
+    ls.push(vars[1].clone());
+    Ok(Var::List(ls))
+};
+
+pub const DEL: Builtin = |vars, _| {
+    if vars.len() != 2 {
+        return Err(CalcError::Arity { func: String::from("del"), expected: 2, found: vars.len() });
+    }
+
+    let mut ls = list_arg("del", &vars[0])?.clone();
+    ls.remove(list_index("del", ls.len(), &vars[1])?);
+    Ok(Var::List(ls))
+};
+
+// -1, 0 or 1 for real numbers (as the same kind), and z/|z| for complex ones
+pub const SIGN: Builtin = |vars, _| map_unary("sign", vars, |var| {
+    let int_sign = |int: &Int| if int.is_zero() { 0 } else if int.is_negative() { -1 } else { 1 };
+    Ok(match var {
+        Var::Int(int) if int.is_real() => Var::Int(IComplex::from_real(int_sign(&int.re))),
+        Var::Int(int) if int.re.is_zero() => Var::Int(IComplex::new_cardinal(
+            Int::from(0), Int::from(int_sign(&int.im))
+        )), Var::Int(int) => {
+            let num = int.to_fcomplex();
+            Var::Float(num / FComplex::new_cardinal(num.len(), 0.0))
+        }, Var::Rational(ratio) => Var::Int(IComplex::from_real(
+            if ratio.is_negative() { -1 } else { 1 }
+        )), Var::Float(num) if num.is_real() => Var::Float(FComplex::new_cardinal(
+            if num.re == 0.0 { 0.0 } else { num.re.signum() }, 0.0
+        )), Var::Float(num) => Var::Float(*num / FComplex::new_cardinal(num.len(), 0.0)),
+        Var::Decimal(num) => Var::Decimal(Decimal::from_i64(
+            if num.is_zero() { 0 } else if num.is_negative() { -1 } else { 1 }, num.prec
+        )), Var::List(_) => unreachable!("Lists are mapped over")
+    })
+});
+
+// Build a complex number from polar form. Both parts have to be real
+pub const COMP: Builtin = |vars, _| {
+    if vars.len() != 2 {
+        return Err(CalcError::Arity { func: String::from("comp"), expected: 2, found: vars.len() });
+    }
+
+    vars[0].clone().do_op(
+        vars[1].clone(),
+        polar,
+        |a, b| Ok(Var::Float(polar(a.to_fcomplex(), b.to_fcomplex())?)),
+        |a, b| Ok(Var::Float(polar(
+            FComplex::new_cardinal(ratio_to_f64(&a), 0.0), FComplex::new_cardinal(ratio_to_f64(&b), 0.0)
+        )?)), |a, b| Ok(Var::Float(polar(
+            FComplex::new_cardinal(a.to_f64(), 0.0), FComplex::new_cardinal(b.to_f64(), 0.0)
+        )?))
+    )
 };
 
-pub const E: fn(&Vec<Var>)->Result<Var, String> = |vars| {
-    if vars.len() != 0 {
-        Err(String::from("e() does not take arguments."))
+// n! for whole, non-negative ints. Grows into a big int as needed
+pub const FACT: Builtin = |vars, _| map_unary("fact", vars, |var| {
+    let n = natural_arg("fact", var)?;
+    check_result_bits("fact", log2_fact(n))?;
+    let mut result = Int::from(1);
+    for i in 2..=n {
+        result = result * Int::from(i);
+    }
+    Ok(Var::Int(IComplex::new_cardinal(result, Int::from(0))))
+});
+
+// n choose k, i.e. n! / (k!(n - k)!), but without computing the giant factorials
+pub const CHOOSE: Builtin = |vars, _| {
+    if vars.len() != 2 {
+        return Err(CalcError::Arity { func: String::from("choose"), expected: 2, found: vars.len() });
+    }
+
+    let n = natural_arg("choose", &vars[0])?;
+    let k = natural_arg("choose", &vars[1])?;
+    let mut result = Int::from(1);
+    if k <= n {
+        check_result_bits("choose", log2_fact(n) - log2_fact(k) - log2_fact(n - k))?;
+
+        // Each partial product is itself a binomial coefficient, so the division is exact
+        let k = k.min(n - k);
+        for i in 0..k {
+            result = (result * Int::from(n - i)).div_floor(&Int::from(i + 1));
+        }
     } else {
-        Ok(Var {
-            ls_data: None,
-            num_data: Some(FComplex::new_polar(consts::E, 0.0)),
-            int_data: None
-        })
+        result = Int::from(0);
+    }
+    Ok(Var::Int(IComplex::new_cardinal(result, Int::from(0))))
+};
+
+// Floor division for ints, i.e. what '/' does before it turns the leftovers into a fraction
+pub const DIV: Builtin = |vars, _| {
+    if vars.len() != 2 {
+        return Err(CalcError::Arity { func: String::from("div"), expected: 2, found: vars.len() });
     }
+
+    vars[0].clone().do_op(
+        vars[1].clone(),
+        |_, _| Err(CalcError::TypeMismatch(String::from("'div' only works on integers"))),
+        |a, b| if b == IComplex::zero() { Err(CalcError::DivisionByZero) } else { Ok(Var::Int(a / b)) },
+        |_, _| Err(CalcError::TypeMismatch(String::from("'div' only works on integers"))),
+        |_, _| Err(CalcError::TypeMismatch(String::from("'div' only works on integers")))
+    )
 };
 
-pub const PI: fn(&Vec<Var>)->Result<Var, String> = |vars| {
-    if vars.len() != 0 {
-        Err(String::from("pi() does not take arguments."))
+pub const FLOAT: Builtin = |vars, settings| {
+    if vars.len() != 1 {
+        Err(CalcError::Arity { func: String::from("float"), expected: 1, found: vars.len() })
     } else {
-        Ok(Var {
-            ls_data: None,
-            num_data: Some(FComplex::new_polar(consts::PI, 0.0)),
-            int_data: None
+        Ok(match &vars[0] {
+            Var::List(ls) => Var::List(ls.iter().map(|var| to_float_kind(var, settings)).collect()),
+            var => to_float_kind(var, settings)
         })
     }
 };
 
-pub const ASIN: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const ACOS: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const ATAN: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const D2R: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const R2D: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const LOG: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const LN: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const MOD: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const FLOOR: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const CEIL: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const ABS: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const IDX: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const APP: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const DEL: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const SIGN: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
-pub const COMP: fn(&Vec<Var>)->Result<Var, String> = |_| Err(String::from("Not implemented"));
+// Whichever float the session uses. Complex values can only be binary floats
+fn to_float_kind(var: &Var, settings: &Settings) -> Var {
+    match (settings.float_mode, var) {
+        (_, Var::Float(_) | Var::Decimal(_)) => var.clone(),
+        (FloatMode::Decimal, var) => match var.to_decimal(settings.precision) {
+            Var::Decimal(num) => Var::Decimal(num),
+            _ => var.to_float()
+        }, (FloatMode::Binary, var) => var.to_float()
+    }
+}
+
+// Read the low n bits of x as a signed int, like loading a smaller register into a bigger one
+pub const SEXT: Builtin = |vars, _| bits_fn("sext", vars, true);
 
+// Keep just the low n bits of x, like storing into a smaller register
+pub const TRUNC: Builtin = |vars, _| bits_fn("trunc", vars, false);
+
+// Shared by sext and trunc, which only differ in how the top bit is read
+fn bits_fn(func: &str, vars: &[Var], signed: bool) -> Result<Var, CalcError> {
+    if vars.len() != 2 {
+        return Err(CalcError::Arity { func: String::from(func), expected: 2, found: vars.len() });
+    }
+
+    let word = u32::try_from(natural_arg(func, &vars[1])?).ok()
+        .and_then(|bits| Word::new(bits, signed))
+        .ok_or_else(|| CalcError::Domain(format!("'{}' needs from 1 to {} bits", func, MAX_BITS)))?;
+    map_items(&vars[0], &|var| match var {
+        Var::Int(int) if int.is_real() => Ok(Var::Int(IComplex::new_cardinal(word.wrap(&int.re), Int::from(0)))),
+        _ => Err(CalcError::TypeMismatch(format!("'{}' only works on real ints", func)))
+    })
+}
+
+// Check for exactly one argument and apply f to it, or to every item if it's a list
+fn map_unary<F: Fn(&Var) -> Result<Var, CalcError>>(
+        func: &str, vars: &[Var], f: F) -> Result<Var, CalcError> {
+    if vars.len() != 1 {
+        return Err(CalcError::Arity { func: String::from(func), expected: 1, found: vars.len() });
+    }
+    map_items(&vars[0], &f)
+}
+
+fn map_items<F: Fn(&Var) -> Result<Var, CalcError>>(var: &Var, f: &F) -> Result<Var, CalcError> {
+    match var {
+        Var::List(ls) => Ok(Var::List(
+            ls.iter().map(|item| map_items(item, f)).collect::<Result<Vec<Var>, CalcError>>()?
+        )), var => f(var)
+    }
+}
+
+// Run a function that needs floats. Exact values get promoted first, and decimals that would
+// leave the real line (e.g. asin(2.0) or ln(-1.0)) fall back to complex floats
+fn float_fn(
+        func: &str, var: &Var, settings: &Settings,
+        float_op: fn(FComplex) -> FComplex,
+        dec_op: fn(&Decimal) -> Option<Decimal>) -> Result<Var, CalcError> {
+    let num = match to_float_kind(var, settings) {
+        Var::Decimal(num) => match dec_op(&num) {
+            Some(res) => return Ok(Var::Decimal(res)),
+            None => FComplex::new_cardinal(num.to_f64(), 0.0)
+        }, Var::Float(num) => num,
+        _ => unreachable!("Numbers always promote to a float")
+    };
+
+    let res = float_op(num);
+    if res.re.is_finite() && res.im.is_finite() {
+        Ok(Var::Float(res))
+    } else {
+        Err(CalcError::Domain(format!("'{}' is undefined at {}", func, var.to_string(settings))))
+    }
+}
+
+fn polar(len: FComplex, angle: FComplex) -> Result<FComplex, CalcError> {
+    if len.is_real() && angle.is_real() {
+        Ok(FComplex::new_polar(len.re, angle.re))
+    } else {
+        Err(CalcError::Domain(String::from("'comp' needs a real radius and angle")))
+    }
+}
+
+fn list_arg<'a>(func: &str, var: &'a Var) -> Result<&'a Vec<Var>, CalcError> {
+    match var {
+        Var::List(ls) => Ok(ls),
+        _ => Err(CalcError::TypeMismatch(format!("'{}' expects a list as its first argument", func)))
+    }
+}
+
+// Turn an index argument into a position in a list of length len
+fn list_index(func: &str, len: usize, var: &Var) -> Result<usize, CalcError> {
+    let int = match var {
+        Var::Int(int) if int.is_real() => &int.re,
+        _ => return Err(CalcError::TypeMismatch(format!("'{}' expects a real integer index", func)))
+    };
+    let pos = int.to_i64().map(|idx| if idx < 0 { idx + len as i64 } else { idx });
+    match pos {
+        Some(pos) if pos >= 0 && pos < len as i64 => Ok(pos as usize),
+        _ => Err(CalcError::Domain(format!(
+            "Index {} is out of range for a list of length {}", int, len
+        )))
+    }
+}
+
+// Pull a whole, non-negative, reasonably sized int out of an argument
+fn natural_arg(func: &str, var: &Var) -> Result<i64, CalcError> {
+    match var {
+        Var::Int(int) if int.is_real() && !int.re.is_negative() => int.re.to_i64().ok_or_else(||
+            CalcError::Domain(format!("{} is too large for '{}'", int.to_string(), func))
+        ), Var::Int(_) => Err(CalcError::Domain(format!(
+            "'{}' needs non-negative real integers", func
+        ))), _ => Err(CalcError::TypeMismatch(format!("'{}' only works on integers", func)))
+    }
+}
+
+// log2(n!) from Stirling's approximation, which is plenty close for sizing up the result
+fn log2_fact(n: i64) -> f64 {
+    if n < 2 {
+        return 0.0;
+    }
+    let n = n as f64;
+    (n * n.ln() - n + 0.5 * (2.0 * consts::PI * n).ln()) / consts::LN_2
+}
+
+// Same limit as powers, so building the result can't take forever
+fn check_result_bits(func: &str, bits: f64) -> Result<(), CalcError> {
+    if bits > MAX_RESULT_BITS as f64 {
+        Err(CalcError::Domain(format!(
+            "The result of '{}' would be over {} bits, which is too large", func, MAX_RESULT_BITS
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{
+        PI, FRAC_PI_2, FRAC_PI_3, FRAC_PI_4, FRAC_PI_6, SQRT_2
+    };
+    use super::{
+        BUILTIN_FUNCS, BUILTIN_HELP
+    };
+    use crate::{
+        var::Var,
+        parser::parse_stmt,
+        settings::{
+            Settings, FloatMode
+        }, eval::{
+            eval, Environment, Outcome
+        }
+    };
+
+    fn calc_in(expr: &str, env: &mut Environment) -> Result<Var, String> {
+        match eval(&parse_stmt(expr).unwrap(), env) {
+            Ok(Outcome::Value(val)) => Ok(val),
+            Ok(other) => panic!("{} is not an expression: {:?}", expr, other),
+            Err(err) => Err(err.to_string())
+        }
+    }
+
+    fn calc(expr: &str) -> String {
+        match calc_in(expr, &mut Environment::new()) {
+            Ok(val) => val.to_string(&Settings::new()),
+            Err(err) => format!("Error: {}", err)
+        }
+    }
+
+    fn calc_decimal(expr: &str) -> String {
+        let mut env = Environment::new();
+        env.settings.float_mode = FloatMode::Decimal;
+        env.settings.precision = 20;
+        calc_in(expr, &mut env).unwrap().to_string(&env.settings)
+    }
+
+    fn assert_close(expr: &str, re: f64, im: f64) {
+        let num = match calc_in(expr, &mut Environment::new()) {
+            Ok(Var::Float(num)) => num,
+            other => panic!("{} gave {:?} instead of a float", expr, other)
+        };
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-12;
+        assert!(
+            close(num.re, re) && close(num.im, im),
+            "{} gave {} + j{} instead of {} + j{}", expr, num.re, num.im, re, im
+        );
+    }
+
+    #[test]
+    fn every_builtin_has_help() {
+        for (name, _) in BUILTIN_FUNCS {
+            assert!(BUILTIN_HELP.iter().any(|(help, _, _)| *help == name), "No help for '{}'", name);
+        }
+    }
+
+    #[test]
+    fn complex_sin_cos() {
+        assert_close("sin(1.0+j1.0)", 1.2984575814159773, 0.6349639147847361);
+        assert_close("cos(1.0+j1.0)", 0.8337300251311491, -0.9888977057628651);
+        assert_close("tan(j1.0)", 0.0, 0.7615941559557649);
+    }
+
+    #[test]
+    fn inverse_trig() {
+        assert_close("asin(1_/2_)", FRAC_PI_6, 0.0);
+        assert_close("asin(2.0)", FRAC_PI_2, -1.3169578969248166);
+        assert_close("acos(0.5)", FRAC_PI_3, 0.0);
+        assert_close("acos(2.0)", 0.0, 1.3169578969248166);
+        assert_close("atan(1_)", FRAC_PI_4, 0.0);
+        assert_close("atan(1.0+j1.0)", 1.0172219678978514, 0.4023594781085251);
+        assert_eq!(calc("atan(j1.0)"), "Error: Domain error: 'atan' is undefined at j1");
+        assert_eq!(calc("asin([0_, 1.0])"), "[ 0 1.5707963267949 ]");
+    }
+
+    #[test]
+    fn logs() {
+        assert_eq!(calc("log(1000_)"), "3");
+        assert_close("ln(e())", 1.0, 0.0);
+        assert_close("ln(0.0-1.0)", 0.0, PI);
+        assert_close("log(j1.0)", 0.0, 0.6821881769209206);
+        assert_eq!(calc("ln(0_)"), "Error: Domain error: 'ln' is undefined at 0");
+    }
+
+    #[test]
+    fn angles() {
+        assert_close("d2r(180_)", PI, 0.0);
+        assert_close("r2d(pi()/2.0)", 90.0, 0.0);
+        assert_eq!(calc("r2d([0_, pi()])"), "[ 0 180 ]");
+    }
+
+    #[test]
+    fn modulus() {
+        assert_eq!(calc("mod(7_, 3_)"), "1");
+        assert_eq!(calc("mod(0_-7_, 3_)"), "2");
+        assert_eq!(calc("mod(7_, 0_-3_)"), "-2");
+        assert_eq!(calc("mod(5_+j3_, 2_)"), "-1 - j1");
+        assert_eq!(calc("mod(7_/2_, 1_)"), "1/2");
+        assert_eq!(calc("mod(7.5, 2.0)"), "1.5");
+        assert_eq!(calc("mod([5_, 6_], 4_)"), "[ 1 2 ]");
+        assert_eq!(calc("mod(1_, 0_)"), "Error: Division by zero");
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(calc("floor(7_/2_)"), "3");
+        assert_eq!(calc("ceil(7_/2_)"), "4");
+        assert_eq!(calc("floor(0.0-2.5)"), "-3");
+        assert_eq!(calc("ceil(2.1)"), "3");
+        assert_eq!(calc("floor(5_)"), "5");
+        assert_close("floor(1.5+j2.5)", 1.0, 2.0);
+    }
+
+    #[test]
+    fn absolute() {
+        assert_eq!(calc("abs(0_-5_)"), "5");
+        assert_eq!(calc("abs(3_+j4_)"), "5");
+        assert_eq!(calc("abs(0_-3_/4_)"), "3/4");
+        assert_eq!(calc("abs([0.0-2.0, 1_])"), "[ 2 1 ]");
+        assert_close("abs(1_+j1_)", SQRT_2, 0.0);
+    }
+
+    #[test]
+    fn signs() {
+        assert_eq!(calc("sign(0_-5_)"), "-1");
+        assert_eq!(calc("sign(0_)"), "0");
+        assert_eq!(calc("sign(2_/3_)"), "1");
+        assert_eq!(calc("sign(0.0-0.5)"), "-1");
+        assert_eq!(calc("sign(j3_)"), "j1");
+        assert_close("sign(3_+j4_)", 0.6, 0.8);
+    }
+
+    #[test]
+    fn list_ops() {
+        assert_eq!(calc("idx([1, 2, 3], 0_)"), "1");
+        assert_eq!(calc("idx([1, 2, 3], 0_-1_)"), "3");
+        assert_eq!(calc("idx([1, 2, 3], 3_)"), "Error: Domain error: Index 3 is out of range for a list of length 3");
+        assert_eq!(calc("idx([1, 2, 3], 1.0)"), "Error: Type mismatch: 'idx' expects a real integer index");
+        assert_eq!(calc("app([1, 2], [3])"), "[ 1 2 [ 3 ] ]");
+        assert_eq!(calc("del([1, 2, 3], 1_)"), "[ 1 3 ]");
+        assert_eq!(calc("del(1, 0_)"), "Error: Type mismatch: 'del' expects a list as its first argument");
+    }
+
+    #[test]
+    fn factorials() {
+        assert_eq!(calc("fact(0_)"), "1");
+        assert_eq!(calc("fact(20_)"), "2432902008176640000");
+        assert_eq!(calc("fact(1000_)").len(), 2568);
+        assert_eq!(calc("choose(5_, 2_)"), "10");
+        assert_eq!(calc("choose(2_, 5_)"), "0");
+        assert_eq!(calc("choose(100000000000_, 2_)"), "4999999999950000000000");
+        assert_eq!(calc("choose(100000000000_, 99999999999_)"), "100000000000");
+
+        // Too big to ever finish building, like huge powers
+        let too_large = |func: &str| format!(
+            "Error: Domain error: The result of '{}' would be over 1048576 bits, which is too large", func
+        );
+        assert_eq!(calc("fact(100000000_)"), too_large("fact"));
+        assert_eq!(calc("choose(100000000000_, 50000000000_)"), too_large("choose"));
+        assert_eq!(calc("choose(10000000_, 1000000_)"), too_large("choose"));
+    }
+
+    #[test]
+    fn complex_from_polar() {
+        assert_close("comp(2_, pi()/2.0)", 0.0, 2.0);
+        assert_close("comp(1_/2_, 0_)", 0.5, 0.0);
+        assert_eq!(calc("comp(j1.0, 1.0)"), "Error: Domain error: 'comp' needs a real radius and angle");
+    }
+
+    #[test]
+    fn decimals() {
+        assert_eq!(calc_decimal("asin(0.5)"), "0.52359877559829887308");
+        assert_eq!(calc_decimal("atan(1_)"), "0.78539816339744830962");
+        assert_eq!(calc_decimal("ln(2.0)"), "0.69314718055994530942");
+        assert_eq!(calc_decimal("log(1000.0)"), "3");
+        assert_eq!(calc_decimal("floor(0.0-2.5)"), "-3");
+        assert_eq!(calc_decimal("mod(7.5, 2.0)"), "1.5");
+        assert_eq!(calc_decimal("sign(0.0-3.0)"), "-1");
+    }
+
+    #[test]
+    fn sext_trunc() {
+        assert_eq!(calc("sext(0xFF_, 8_)"), "-1");
+        assert_eq!(calc("sext(0x7F_, 8_)"), "127");
+        assert_eq!(calc("trunc(0x1FF_, 8_)"), "255");
+        assert_eq!(calc("trunc(0_ - 1_, 4_)"), "15");
+        assert_eq!(calc("sext(1.0, 8_)"), "Error: Type mismatch: 'sext' only works on real ints");
+    }
+}
@@ -0,0 +1,140 @@
+/*
+ * Author: Dylan Turner
+ * Description:
+ * - Lines starting with ':' are commands to the calculator itself instead of math
+ * - They work the same in the repl, from the command line, and in the init file
+ */
+
+use crate::{
+    error::CalcError,
+    eval::Environment,
+    builtin::BUILTIN_HELP
+};
+
+// Usage and description of each command, for ':help'
+const COMMANDS: [(&str, &str); 10] = [
+    (":vars", "list variables"),
+    (":results", "list earlier results, usable as _1, _2, etc (the last is also ans)"),
+    (":funcs", "list user defined functions"),
+    (":show <name>", "show a variable, function or builtin"),
+    (":del <name>", "remove a variable or function"),
+    (":clear", "remove every variable and function"),
+    (":set <name> <value>", "change a setting"),
+    (":help [builtin]", "show this, or how to use a builtin"),
+    (":quit", "leave (so do 'q', Ctrl-D and Ctrl-C)"),
+    (":q", "same as :quit")
+];
+
+pub fn is_command(line: &str) -> bool {
+    line.trim_start().starts_with(':')
+}
+
+// Lines that end the session. Only whole lines count so identifiers with a 'q' in them still work
+pub fn is_quit(line: &str) -> bool {
+    matches!(line.trim(), "q" | ":q" | ":quit")
+}
+
+// Run a command line, giving back a message to show the user
+pub fn run_command(line: &str, env: &mut Environment) -> Result<String, CalcError> {
+    let words = line.trim_start()[1..].split_whitespace().collect::<Vec<&str>>();
+    match words.as_slice() {
+        [ "vars" ] => Ok(list_vars(env)),
+        [ "funcs" ] => Ok(list_funcs(env)),
+        [ "results" ] => Ok(list_results(env)),
+        [ "show", name ] => show(name, env),
+        [ "del", name ] => if env.vars.remove(*name).is_some() {
+            Ok(format!("Deleted var '{}'", name))
+        } else if env.funcs.remove(*name).is_some() {
+            Ok(format!("Deleted function '{}'", name))
+        } else {
+            Err(CalcError::UnknownVariable(String::from(*name)))
+        }, [ "clear" ] => {
+            let msg = format!("Deleted {} vars and {} functions", env.vars.len(), env.funcs.len());
+            env.vars.clear();
+            env.funcs.clear();
+            Ok(msg)
+        }, [ "help" ] => Ok(help()),
+        [ "help", name ] => match BUILTIN_HELP.iter().find(|(builtin, _, _)| builtin == name) {
+            None => Err(CalcError::UnknownFunction(String::from(*name))),
+            Some((_, usage, desc)) => Ok(format!("{} - {}", usage, desc))
+        }, [ "set", name, value ] => {
+            let msg = env.settings.set(name, value)?;
+            env.trim_results();
+            Ok(msg)
+        }, [ "q" | "quit" ] => Err(CalcError::InvalidCommand(String::from(
+            "Quitting only works in the repl or from the command line"
+        ))), [ name, .. ] => {
+            // Either a real command that was used wrong, or a typo
+            let cmd = format!(":{}", name);
+            match COMMANDS.iter().find(|(usage, _)| usage.split(' ').next() == Some(cmd.as_str())) {
+                Some((usage, _)) => Err(CalcError::InvalidCommand(format!("Usage: {}", usage))),
+                None => Err(CalcError::InvalidCommand(format!("No such command '{}'", cmd)))
+            }
+        }, [] => Err(CalcError::InvalidCommand(String::from("Missing command after ':'")))
+    }
+}
+
+// Sorted by name so the output is the same every time
+fn list_vars(env: &Environment) -> String {
+    let mut names = env.vars.keys().collect::<Vec<&String>>();
+    names.sort();
+    if names.is_empty() {
+        return String::from("No variables");
+    }
+    names.iter().map(|name| format!("{} = {}", name, env.vars[*name].to_string(&env.settings)))
+        .collect::<Vec<String>>().join("\n")
+}
+
+fn list_results(env: &Environment) -> String {
+    if env.results.is_empty() {
+        return String::from("No results");
+    }
+    env.numbered_results().map(|(num, val)| format!("_{} = {}", num, val.to_string(&env.settings)))
+        .collect::<Vec<String>>().join("\n")
+}
+
+fn list_funcs(env: &Environment) -> String {
+    let mut names = env.funcs.keys().collect::<Vec<&String>>();
+    names.sort();
+    if names.is_empty() {
+        return String::from("No functions");
+    }
+    names.iter().map(|name| show_func(name, env)).collect::<Vec<String>>().join("\n")
+}
+
+// Same form it was defined with, e.g. \inc(x) -> x + 1
+fn show_func(name: &str, env: &Environment) -> String {
+    let func = &env.funcs[name];
+    format!("\\{}({}) -> {}", name, func.args.join(", "), func.expr)
+}
+
+fn show(name: &str, env: &Environment) -> Result<String, CalcError> {
+    let mut found = Vec::new();
+    if let Some(val) = env.vars.get(name) {
+        found.push(format!("{} = {}", name, val.to_string(&env.settings)));
+    }
+    if env.funcs.contains_key(name) {
+        found.push(show_func(name, env));
+    }
+    if let Some((_, usage, desc)) = BUILTIN_HELP.iter().find(|(builtin, _, _)| *builtin == name) {
+        found.push(format!("{} - {} (builtin)", usage, desc));
+    }
+
+    if found.is_empty() {
+        Err(CalcError::UnknownVariable(String::from(name)))
+    } else {
+        Ok(found.join("\n"))
+    }
+}
+
+fn help() -> String {
+    let mut lines = vec![ String::from("Commands:") ];
+    for (usage, desc) in COMMANDS {
+        lines.push(format!("  {:<20} {}", usage, desc));
+    }
+    lines.push(String::from("Builtins:"));
+    for (_, usage, desc) in BUILTIN_HELP {
+        lines.push(format!("  {:<20} {}", usage, desc));
+    }
+    lines.join("\n")
+}
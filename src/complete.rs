@@ -0,0 +1,88 @@
+/*
+ * Author: Dylan Turner
+ * Description:
+ * - Tab completion for the repl
+ * - Knows about variables, user functions, builtins and plugins for 'call'
+ * - Also where the editor gets its highlighting from
+ */
+
+use crate::{
+    eval::Environment,
+    builtin::BUILTIN_HELP,
+    plugin::plugin_names,
+    highlight::highlight
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub word: String,
+    pub args: Option<String> // Functions get '(' after them and these args shown as a hint
+}
+
+// Things the editor needs to know about what's being typed
+pub trait LineHelper {
+    // Possible words to put in place of the identifier ending at the cursor (given as a char index)
+    fn complete(&self, line: &[char], cursor: usize) -> (usize, Vec<Candidate>);
+
+    // The line as it should be drawn, e.g. with colors
+    fn highlight(&self, line: &[char], cursor: usize) -> String;
+}
+
+impl LineHelper for Environment {
+    fn complete(&self, line: &[char], cursor: usize) -> (usize, Vec<Candidate>) {
+        let mut start = cursor;
+        while start > 0 && (line[start - 1].is_alphanumeric() || line[start - 1] == '_') {
+            start -= 1;
+        }
+        let prefix = line[start..cursor].iter().collect::<String>();
+        let before = line[..start].iter().collect::<String>();
+
+        let mut cands = Vec::new();
+        if before.trim_end().ends_with("call(") {
+            // The first argument to 'call' is a library name, not an expression
+            for name in plugin_names() {
+                cands.push(Candidate { word: name, args: None });
+            }
+        } else {
+            for name in self.vars.keys() {
+                cands.push(Candidate { word: name.clone(), args: None });
+            }
+            if self.ans.is_some() {
+                cands.push(Candidate { word: String::from("ans"), args: None });
+            }
+            for (name, func) in self.funcs.iter() {
+                cands.push(Candidate { word: name.clone(), args: Some(func.args.join(", ")) });
+            }
+            for (name, usage, _) in BUILTIN_HELP {
+                // Usage looks like mod(x, y), so the args are what's in the parentheses
+                let args = usage.trim_start_matches(name).trim_start_matches('(').trim_end_matches(')');
+                cands.push(Candidate { word: String::from(name), args: Some(String::from(args)) });
+            }
+        }
+
+        cands.retain(|cand| cand.word.starts_with(prefix.as_str()));
+        cands.sort_by(|a, b| a.word.cmp(&b.word));
+        cands.dedup_by(|a, b| a.word == b.word);
+        (start, cands)
+    }
+
+    fn highlight(&self, line: &[char], cursor: usize) -> String {
+        highlight("", line.iter().collect::<String>().as_str(), cursor, self)
+    }
+}
+
+// For the lines after the first of a statement that's continued, so brackets opened earlier still match
+pub struct Continued<'a> {
+    pub env: &'a Environment,
+    pub before: String // The earlier lines, each ending in '\n'
+}
+
+impl LineHelper for Continued<'_> {
+    fn complete(&self, line: &[char], cursor: usize) -> (usize, Vec<Candidate>) {
+        self.env.complete(line, cursor)
+    }
+
+    fn highlight(&self, line: &[char], cursor: usize) -> String {
+        highlight(self.before.as_str(), line.iter().collect::<String>().as_str(), cursor, self.env)
+    }
+}
@@ -6,121 +6,196 @@
 use std::{
     f64::consts::PI,
     ops::{
-        Add, Sub, Mul, Div, BitXor
+        Add, Sub, Mul, Div, Rem, BitXor
     }, cmp::Ordering
 };
+use crate::int::Int;
 
+// Stored as real and imaginary parts so real-only math never touches trig
 #[derive(Debug, Clone, Copy)]
 pub struct FComplex {
-    pub len: f64,
-    pub angle: f64
+    pub re: f64,
+    pub im: f64
 }
 
 impl FComplex {
     pub fn new_polar(len: f64, angle: f64) -> Self {
+        // Keep exact values on the real axis instead of picking up sin(0)/cos(pi) noise
+        if angle == 0.0 {
+            Self::new_cardinal(len, 0.0)
+        } else {
+            Self::new_cardinal(len * angle.cos(), len * angle.sin())
+        }
+    }
+
+    pub fn new_cardinal(re: f64, im: f64) -> Self {
         Self {
-            len, angle        
+            re, im
         }
     }
 
-    pub fn new_cardinal(real: f64, lateral: f64) -> Self {
-        let len = (real.powf(2.0) + lateral.powf(2.0)).sqrt();
-        let angle = if real == 0.0 {
-            PI / 2.0
-        } else if real > 0.0 {
-            (lateral / real).atan()
+    pub fn to_cardinal(self) -> (f64, f64) {
+        (self.re, self.im)
+    }
+
+    pub fn len(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn angle(self) -> f64 {
+        // Checked by hand so -0.0 imaginary parts don't flip the negative axis to -pi
+        if self.im == 0.0 {
+            if self.re < 0.0 { PI } else { 0.0 }
         } else {
-            (lateral / real).atan() + PI
-        };
+            self.im.atan2(self.re)
+        }
+    }
 
-        Self::new_polar(len, angle)
+    pub fn is_real(self) -> bool {
+        self.im == 0.0
     }
 
-    pub fn to_cardinal(&self) -> (f64, f64) {
-        let real = self.len * self.angle.cos();
-        let lateral = self.len * self.angle.sin();
-        (real, lateral)
+    pub fn zero() -> Self {
+        Self::new_cardinal(0.0, 0.0)
     }
-    
-    pub fn to_string(&self) -> String {
-        if self.angle != 0.0 {
-            format!("{}∠{}", self.len, self.angle)    
+
+    // Principal branch: ln z = ln|z| + j*arg(z)
+    pub fn ln(self) -> Self {
+        Self::new_cardinal(self.len().ln(), self.angle())
+    }
+
+    pub fn exp(self) -> Self {
+        Self::new_polar(self.re.exp(), self.im)
+    }
+
+    pub fn log10(self) -> Self {
+        if self.is_real() && self.re > 0.0 {
+            Self::new_cardinal(self.re.log10(), 0.0)
         } else {
-            format!("{}", self.len)
+            self.ln() / Self::new_cardinal(10.0_f64.ln(), 0.0)
         }
     }
 
-    pub fn zero() -> Self {
-        Self::new_polar(0.0, 0.0)
+    // Principal square root, i.e. half the angle
+    pub fn sqrt(self) -> Self {
+        if self.is_real() && self.re >= 0.0 {
+            Self::new_cardinal(self.re.sqrt(), 0.0)
+        } else {
+            Self::new_polar(self.len().sqrt(), self.angle() / 2.0)
+        }
+    }
+
+    /*
+     * sin(a + jb) = sin(a)cosh(b) + j*cos(a)sinh(b)
+     * cos(a + jb) = cos(a)cosh(b) - j*sin(a)sinh(b)
+     */
+    pub fn sin(self) -> Self {
+        if self.is_real() {
+            Self::new_cardinal(self.re.sin(), 0.0)
+        } else {
+            Self::new_cardinal(self.re.sin() * self.im.cosh(), self.re.cos() * self.im.sinh())
+        }
+    }
+
+    pub fn cos(self) -> Self {
+        if self.is_real() {
+            Self::new_cardinal(self.re.cos(), 0.0)
+        } else {
+            Self::new_cardinal(self.re.cos() * self.im.cosh(), -self.re.sin() * self.im.sinh())
+        }
+    }
+
+    // Principal branch: asin z = -j*ln(jz + sqrt(1 - z^2))
+    pub fn asin(self) -> Self {
+        if self.is_real() && self.re.abs() <= 1.0 {
+            return Self::new_cardinal(self.re.asin(), 0.0);
+        }
+        let j = Self::new_cardinal(0.0, 1.0);
+        let one = Self::new_cardinal(1.0, 0.0);
+        Self::new_cardinal(0.0, -1.0) * (j * self + (one - self * self).sqrt()).ln()
+    }
+
+    pub fn acos(self) -> Self {
+        if self.is_real() && self.re.abs() <= 1.0 {
+            return Self::new_cardinal(self.re.acos(), 0.0);
+        }
+        Self::new_cardinal(PI / 2.0, 0.0) - self.asin()
+    }
+
+    // Principal branch: atan z = (j/2)(ln(1 - jz) - ln(1 + jz)). Undefined at z = +/-j
+    pub fn atan(self) -> Self {
+        if self.is_real() {
+            return Self::new_cardinal(self.re.atan(), 0.0);
+        }
+        let jz = Self::new_cardinal(0.0, 1.0) * self;
+        let one = Self::new_cardinal(1.0, 0.0);
+        Self::new_cardinal(0.0, 0.5) * ((one - jz).ln() - (one + jz).ln())
+    }
+
+    pub fn floor(self) -> Self {
+        Self::new_cardinal(self.re.floor(), self.im.floor())
+    }
+
+    pub fn ceil(self) -> Self {
+        Self::new_cardinal(self.re.ceil(), self.im.ceil())
     }
 }
 
 impl Add for FComplex {
     type Output = Self;
     fn add(self, other: Self) -> Self {
-        let (r1, l1) = self.to_cardinal();
-        let (r2, l2) = other.to_cardinal();
-        Self::new_cardinal(r1 + r2, l1 + l2)
+        Self::new_cardinal(self.re + other.re, self.im + other.im)
     }
 }
 
 impl Sub for FComplex {
     type Output = Self;
     fn sub(self, other: Self) -> Self {
-        let (r1, l1) = self.to_cardinal();
-        let (r2, l2) = other.to_cardinal();
-        Self::new_cardinal(r1 - r2, l1 - l2)
+        Self::new_cardinal(self.re - other.re, self.im - other.im)
     }
 }
 
 impl Mul for FComplex {
     type Output = Self;
     fn mul(self, other: Self) -> Self {
-        Self::new_polar(self.len * other.len, self.angle + other.angle)
+        if self.is_real() && other.is_real() {
+            Self::new_cardinal(self.re * other.re, 0.0)
+        } else {
+            Self::new_cardinal(
+                self.re * other.re - self.im * other.im,
+                self.re * other.im + self.im * other.re
+            )
+        }
     }
 }
 
 impl Div for FComplex {
     type Output = Self;
     fn div(self, other: Self) -> Self {
-        Self::new_polar(self.len / other.len, self.angle - other.angle)
+        if other.is_real() {
+            Self::new_cardinal(self.re / other.re, self.im / other.re)
+        } else {
+            // Multiply top and bottom by the conjugate
+            let denom = other.re * other.re + other.im * other.im;
+            Self::new_cardinal(
+                (self.re * other.re + self.im * other.im) / denom,
+                (self.im * other.re - self.re * other.im) / denom
+            )
+        }
     }
 }
 
 impl PartialEq for FComplex {
     fn eq(&self, other: &Self) -> bool {
-        self.len == other.len && self.angle == other.angle
-    }
-
-    fn ne(&self, other: &Self) -> bool {
-        !self.eq(other)
+        self.re == other.re && self.im == other.im
     }
 }
 
+// Only real numbers have an order. Anything with an imaginary part is unordered
 impl PartialOrd for FComplex {
-    fn lt(&self, other: &Self) -> bool {
-        self.len < other.len
-    }
-
-    fn gt(&self, other: &Self) -> bool {
-        self.len > other.len
-    }
-
-    fn le(&self, other: &Self) -> bool {
-        self.len <= other.len
-    }
-
-    fn ge(&self, other: &Self) -> bool {
-        self.len >= other.len
-    }
-
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.eq(other) {
-            Some(Ordering::Equal)
-        } else if self.le(other) {
-            Some(Ordering::Less)
-        } else if self.ge(other) {
-            Some(Ordering::Greater)
+        if self.is_real() && other.is_real() {
+            self.re.partial_cmp(&other.re)
         } else {
             None
         }
@@ -131,156 +206,208 @@ impl PartialOrd for FComplex {
 impl BitXor for FComplex {
     type Output = Self;
     fn bitxor(self, other: Self) -> Self {
+        // Stay on the real line when we can
+        if self.is_real() && other.is_real() && (self.re >= 0.0 || other.re.fract() == 0.0) {
+            return Self::new_cardinal(self.re.powf(other.re), 0.0);
+        }
+
+        // 0^w is 0 for anything that isn't going to blow up
+        if self == Self::zero() {
+            return Self::zero();
+        }
+
         /*
-         * Polar form is r*e^jtheta, so what is (r*e^jtheta)^(r2*e^jtheta2)?
-         *
          * Say we have complex z and complex w, z^w can be rewritten as e^(w * ln z)
-         * Doing some math yields ln z = ln len(z) + j(2pi + angle(z))
-         *
-         * Which we can plug back in
+         * where ln z = ln len(z) + j*angle(z) (principal branch)
          */
-
-        // ln z = ln len(z) + j(2pi + angle(z))
-        let ln_z = Self::new_cardinal(self.len.ln(), self.angle);
-        
-        // w * ln z
-        let exp = other * ln_z;
-
-        // e^(exp.a + jexp.b) = (e^exp.a, exp.b)
-        let (exp_a, exp_b) = exp.to_cardinal();
-        Self::new_polar(exp_a.exp(), exp_b)
+        (other * self.ln()).exp()
     }
 }
 
-// Do it again with integer math
-#[derive(Debug, Clone, Copy)]
+// Do it again with integer math. Kept as exact (re, im) pairs, i.e. Gaussian integers
+#[derive(Debug, Clone)]
 pub struct IComplex {
-    pub len: i64,
-    pub angle_deg: i64
+    pub re: Int,
+    pub im: Int
 }
 
 impl IComplex {
-    pub fn new_polar(len: i64, angle_deg: i64) -> Self {
+    pub fn new_cardinal(re: Int, im: Int) -> Self {
         Self {
-            len, angle_deg
+            re, im
         }
     }
 
-    pub fn new_cardinal(real: i64, lateral: i64) -> Self {
-        let len = ((real.pow(2) + lateral.pow(2)) as f64).sqrt() as i64;
-        let angle_deg = if real == 0 {
-            90
-        } else if real > 0 {
-            ((lateral as f64 / real as f64).atan() * 180.0 / PI) as i64
-        } else {
-            (((lateral as f64 / real as f64).atan() + PI) * 180.0 / PI) as i64
-        };
+    pub fn from_real(re: i64) -> Self {
+        Self::new_cardinal(Int::from(re), Int::from(0))
+    }
 
-        Self::new_polar(len, angle_deg)
+    pub fn to_fcomplex(&self) -> FComplex {
+        FComplex::new_cardinal(self.re.to_f64(), self.im.to_f64())
     }
 
-    pub fn to_cardinal(&self) -> (i64, i64) {
-        let real = self.len * ((self.angle_deg as f64 * PI / 180.0).cos() as i64);
-        let lateral = self.len * ((self.angle_deg as f64 * PI / 180.0).sin() as i64);
-        (real, lateral)
+    pub fn is_real(&self) -> bool {
+        self.im.is_zero()
     }
 
-    pub fn to_fcomplex(&self) -> FComplex {
-        FComplex::new_polar(self.len as f64, (self.angle_deg as f64) * PI / 180.0)
+    pub fn conj(&self) -> Self {
+        Self::new_cardinal(self.re.clone(), -self.im.clone())
+    }
+
+    // |z|^2, which unlike |z| is always an integer
+    pub fn norm(&self) -> Int {
+        self.re.clone() * self.re.clone() + self.im.clone() * self.im.clone()
     }
-     
+
     pub fn to_string(&self) -> String {
-        if self.angle_deg != 0 {
-            format!("{}∠{}°", self.len, self.angle_deg)    
+        if self.is_real() {
+            format!("{}", self.re)
+        } else if self.re.is_zero() {
+            format!("j{}", self.im)
+        } else if self.im.is_negative() {
+            format!("{} - j{}", self.re, -self.im.clone())
         } else {
-            format!("{}", self.len)
+            format!("{} + j{}", self.re, self.im)
         }
     }
 
     pub fn zero() -> Self {
-        Self::new_polar(0, 0)
+        Self::from_real(0)
+    }
+
+    // The exact quotient is a*conj(b)/|b|^2, and each part is rounded with floor(x + 1/2)
+    fn div_nearest(&self, other: &Self) -> Self {
+        let norm = other.norm();
+        let round = |num: Int| (num * Int::from(2) + norm.clone()).div_floor(&(norm.clone() * Int::from(2)));
+        let num = self.clone() * other.conj();
+        Self::new_cardinal(round(num.re), round(num.im))
+    }
+
+    // 0, 1, -1, j and -j, which stay that size whatever power they're raised to
+    pub fn is_unit_or_zero(&self) -> bool {
+        self.norm() <= Int::from(1)
+    }
+
+    // Exact z^n. Real numbers get Int's fast path, complex ones go by squaring
+    pub fn pow(&self, mut n: u64) -> Self {
+        // Those all repeat every 4 powers, so only where n lands in that matters
+        if self.is_unit_or_zero() && n > 4 {
+            return self.pow((n - 1) % 4 + 1);
+        }
+
+        if self.is_real() {
+            return Self::new_cardinal(self.re.pow(n), Int::from(0));
+        }
+
+        let mut base = self.clone();
+        let mut result = Self::from_real(1);
+        while n > 0 {
+            if n & 1 == 1 {
+                result = result * base.clone();
+            }
+            base = base.clone() * base;
+            n >>= 1;
+        }
+        result
     }
 }
 
 impl Add for IComplex {
     type Output = Self;
     fn add(self, other: Self) -> Self {
-        let (r1, l1) = self.to_cardinal();
-        let (r2, l2) = other.to_cardinal();
-        Self::new_cardinal(r1 + r2, l1 + l2)
+        Self::new_cardinal(self.re + other.re, self.im + other.im)
     }
 }
 
 impl Sub for IComplex {
     type Output = Self;
     fn sub(self, other: Self) -> Self {
-        let (r1, l1) = self.to_cardinal();
-        let (r2, l2) = other.to_cardinal();
-        Self::new_cardinal(r1 - r2, l1 - l2)
+        Self::new_cardinal(self.re - other.re, self.im - other.im)
     }
 }
 
 impl Mul for IComplex {
     type Output = Self;
     fn mul(self, other: Self) -> Self {
-        Self::new_polar(self.len * other.len, self.angle_deg + other.angle_deg)
+        Self::new_cardinal(
+            self.re.clone() * other.re.clone() - self.im.clone() * other.im.clone(),
+            self.re * other.im + self.im * other.re
+        )
     }
 }
 
+// Real numbers floor divide as usual. Anything complex goes to the Gaussian int nearest the
+// exact quotient, which is what keeps |a % b| < |b|. Flooring each part could leave nearly 2|b|^2
 impl Div for IComplex {
     type Output = Self;
     fn div(self, other: Self) -> Self {
-        Self::new_polar(self.len / other.len, self.angle_deg - other.angle_deg)
+        if self.is_real() && other.is_real() {
+            Self::new_cardinal(self.re.div_floor(&other.re), Int::from(0))
+        } else {
+            self.div_nearest(&other)
+        }
     }
 }
 
-// NOTE: Not xor but power. Also, ignores lateral
-impl BitXor for IComplex {
+// Whatever's left after floor division, so a = (a / b) * b + a % b
+impl Rem for IComplex {
     type Output = Self;
-    fn bitxor(self, other: Self) -> Self {
-        let result = self.to_fcomplex() ^ other.to_fcomplex();
-        IComplex::new_polar(result.len as i64, (result.angle * 180.0 / PI) as i64)
+    fn rem(self, other: Self) -> Self {
+        self.clone() - (self / other.clone()) * other
     }
 }
 
 impl PartialEq for IComplex {
     fn eq(&self, other: &Self) -> bool {
-        self.len == other.len && self.angle_deg == other.angle_deg
-    }
-
-    fn ne(&self, other: &Self) -> bool {
-        !self.eq(other)
+        self.re == other.re && self.im == other.im
     }
 }
 
+// Same as floats, only real numbers are ordered
 impl PartialOrd for IComplex {
-    fn lt(&self, other: &Self) -> bool {
-        self.len < other.len
-    }
-
-    fn gt(&self, other: &Self) -> bool {
-        self.len > other.len
-    }
-
-    fn le(&self, other: &Self) -> bool {
-        self.len <= other.len
-    }
-
-    fn ge(&self, other: &Self) -> bool {
-        self.len >= other.len
-    }
-
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.eq(other) {
-            Some(Ordering::Equal)
-        } else if self.le(other) {
-            Some(Ordering::Less)
-        } else if self.ge(other) {
-            Some(Ordering::Greater)
+        if self.is_real() && other.is_real() {
+            self.re.partial_cmp(&other.re)
         } else {
             None
         }
     }
 }
 
-
+#[cfg(test)]
+mod tests {
+    use super::IComplex;
+    use crate::int::Int;
+
+    fn gauss(re: i64, im: i64) -> IComplex {
+        IComplex::new_cardinal(Int::from(re), Int::from(im))
+    }
+
+    #[test]
+    fn floor_division() {
+        assert_eq!(gauss(7, 0) / gauss(2, 0), gauss(3, 0));
+        assert_eq!(gauss(-7, 0) / gauss(2, 0), gauss(-4, 0));
+        assert_eq!(gauss(7, 0) % gauss(-2, 0), gauss(-1, 0));
+        assert_eq!(gauss(5, 3) / gauss(2, 0), gauss(3, 2));
+        assert_eq!(gauss(5, 3) % gauss(2, 0), gauss(-1, -1));
+        assert_eq!(gauss(5, 3) / gauss(0, 1), gauss(3, -5));
+        assert_eq!(gauss(7, 5) / gauss(1, 1), gauss(6, -1));
+        assert_eq!(gauss(7, 5) % gauss(1, 1), gauss(0, 0));
+    }
+
+    // a = (a / b) * b + a % b, with a % b always smaller than b, whatever the signs
+    #[test]
+    fn remainders() {
+        let range = -6..=6;
+        for a in range.clone().flat_map(|re| range.clone().map(move |im| gauss(re, im))) {
+            for b in [
+                gauss(3, 0), gauss(-3, 0), gauss(0, 2), gauss(0, -5), gauss(1, 1),
+                gauss(-2, 3), gauss(4, -1), gauss(1, 0), gauss(0, -1)
+            ] {
+                let (q, r) = (a.clone() / b.clone(), a.clone() % b.clone());
+                assert_eq!(q * b.clone() + r.clone(), a, "{} / {}", a.to_string(), b.to_string());
+                assert!(r.norm() < b.norm(), "{} % {} is {}", a.to_string(), b.to_string(), r.to_string());
+            }
+        }
+    }
+}
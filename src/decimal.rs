@@ -0,0 +1,670 @@
+/*
+ * Author: Dylan Turner
+ * Description:
+ * - Base 10 floats with a chosen number of significant digits, i.e. 0.1 + 0.2 really is 0.3
+ * - Stored as mant * 10^exp, and every result gets rounded back to prec digits
+ * - Also has the transcendental functions so builtins can stay in base 10
+ */
+
+use std::{
+    cmp::Ordering,
+    fmt::{
+        Display, Formatter, Result as FmtResult
+    }, ops::{
+        Add, Sub, Mul, Div, Neg
+    }
+};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{
+    Signed, ToPrimitive, Zero, One
+};
+use crate::error::CalcError;
+
+// Extra digits carried through series so the final rounding is still right
+const GUARD_DIGITS: u32 = 10;
+
+// Exponents stay within +/- this, which leaves plenty of room to add digit counts to them
+const MAX_EXP: i64 = 1 << 60;
+
+#[derive(Clone, Debug)]
+pub struct Decimal {
+    pub mant: BigInt,
+    pub exp: i64,
+    pub prec: u32
+}
+
+impl Decimal {
+    pub fn new(mant: BigInt, exp: i64, prec: u32) -> Self {
+        let (mant, exp) = round_digits(mant, exp, prec);
+        Self {
+            mant, exp, prec
+        }
+    }
+
+    pub fn zero(prec: u32) -> Self {
+        Self::new(BigInt::zero(), 0, prec)
+    }
+
+    pub fn one(prec: u32) -> Self {
+        Self::new(BigInt::one(), 0, prec)
+    }
+
+    pub fn from_int(int: &BigInt, prec: u32) -> Self {
+        Self::new(int.clone(), 0, prec)
+    }
+
+    pub fn from_i64(int: i64, prec: u32) -> Self {
+        Self::new(BigInt::from(int), 0, prec)
+    }
+
+    pub fn from_ratio(ratio: &BigRational, prec: u32) -> Self {
+        Self::from_int(ratio.numer(), prec + GUARD_DIGITS)
+            .div_prec(&Self::from_int(ratio.denom(), prec + GUARD_DIGITS), prec)
+            .expect("Whole numbers have small exponents")
+    }
+
+    // Only good for seeding Newton's method and the like
+    fn from_f64(val: f64, prec: u32) -> Self {
+        Self::parse(format!("{:e}", val).as_str(), prec).unwrap_or_else(|| Self::zero(prec))
+    }
+
+    // Takes float literal text like 12.5 or 1.25E1
+    pub fn parse(text: &str, prec: u32) -> Option<Self> {
+        let (digits, e) = match text.find(['e', 'E']) {
+            None => (text, 0),
+            Some(i) => (&text[..i], text[i + 1..].parse::<i64>().ok()?)
+        };
+        let (whole, frac) = match digits.find('.') {
+            None => (digits, ""),
+            Some(i) => (&digits[..i], &digits[i + 1..])
+        };
+        let mant = format!("{}{}", whole, frac).parse::<BigInt>().ok()?;
+        let exp = e.checked_sub(frac.len() as i64).filter(|exp| exp.abs() <= MAX_EXP)?;
+        Some(Self::new(mant, exp, prec))
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        format!("{}e{}", self.mant, self.exp).parse::<f64>().unwrap_or(f64::NAN)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mant.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.mant.is_negative()
+    }
+
+    // Trailing zeros are always stripped, so anything with exp >= 0 is whole
+    pub fn is_integer(&self) -> bool {
+        self.exp >= 0
+    }
+
+    // None if it isn't whole, or has too many zeros to write out
+    pub fn to_integer(&self) -> Option<BigInt> {
+        if self.is_integer() {
+            Some(&self.mant * pow10(u32::try_from(self.exp).ok()?))
+        } else {
+            None
+        }
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::new(self.mant.abs(), self.exp, self.prec)
+    }
+
+    pub fn with_prec(&self, prec: u32) -> Self {
+        Self::new(self.mant.clone(), self.exp, prec)
+    }
+
+    // Number of digits before the decimal point, i.e. floor(log10|x|) + 1
+    fn point(&self) -> i64 {
+        num_digits(&self.mant) as i64 + self.exp
+    }
+
+    /* Arithmetic at an explicit precision. The operators use the larger of the two */
+
+    fn add_prec(&self, other: &Self, prec: u32) -> Self {
+        // Something too small to reach the digits being kept can't change the rounded result,
+        // and lining the two up would take a power of 10 as big as the gap between them
+        // Zero counts as the smallest of all, even though its point is 1
+        let (big, small) = if other.is_zero() || (!self.is_zero() && self.point() >= other.point()) {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        if small.is_zero() || big.point() - small.point() > (prec + GUARD_DIGITS) as i64 {
+            return big.with_prec(prec);
+        }
+
+        let exp = self.exp.min(other.exp);
+        let mant = shift10(&self.mant, self.exp - exp) + shift10(&other.mant, other.exp - exp);
+        Self::new(mant, exp, prec)
+    }
+
+    // Multiplying and dividing add and subtract exponents, which can go past MAX_EXP
+    fn mul_prec(&self, other: &Self, prec: u32) -> Result<Self, CalcError> {
+        let exp = self.exp.checked_add(other.exp).ok_or_else(out_of_range)?;
+        Self::new(&self.mant * &other.mant, exp, prec).in_range()
+    }
+
+    // Caller checks for zero
+    fn div_prec(&self, other: &Self, prec: u32) -> Result<Self, CalcError> {
+        // Scale the top up so the integer quotient has a couple more digits than we keep
+        let shift = (prec as i64 + 2 + num_digits(&other.mant) as i64
+            - num_digits(&self.mant) as i64).max(0);
+        let exp = self.exp.checked_sub(other.exp)
+            .and_then(|exp| exp.checked_sub(shift))
+            .ok_or_else(out_of_range)?;
+        let mant = (&self.mant * pow10(shift as u32)) / &other.mant;
+        Self::new(mant, exp, prec).in_range()
+    }
+
+    fn in_range(self) -> Result<Self, CalcError> {
+        if self.exp.abs() <= MAX_EXP { Ok(self) } else { Err(out_of_range()) }
+    }
+
+    fn div_i64(&self, n: i64, prec: u32) -> Result<Self, CalcError> {
+        self.div_prec(&Self::from_i64(n, prec), prec)
+    }
+
+    /* Transcendental functions. Each one works GUARD_DIGITS past prec and rounds at the end */
+
+    // Only fails when the result is too big or small to hold
+    pub fn exp(&self) -> Result<Self, CalcError> {
+        // e^x has about 0.43x digits, so anything this long is past MAX_EXP either way
+        if self.point() > 19 {
+            return Err(out_of_range());
+        }
+
+        // Halve until small, do the series, then square back up
+        let mut halvings = 0;
+        let mut reduced = self.clone();
+        let half = Self::parse("0.5", self.prec).unwrap();
+        while reduced.abs() > half {
+            reduced = reduced.div_i64(2, self.prec + GUARD_DIGITS + halvings)?;
+            halvings += 1;
+        }
+        let wp = self.prec + GUARD_DIGITS + halvings;
+
+        let mut sum = Self::one(wp);
+        let mut term = Self::one(wp);
+        let mut n = 1;
+        loop {
+            term = term.mul_prec(&reduced, wp)?.div_i64(n, wp)?;
+            if term.is_zero() || term.point() < sum.point() - wp as i64 {
+                break;
+            }
+            sum = sum.add_prec(&term, wp);
+            n += 1;
+        }
+
+        for _ in 0..halvings {
+            sum = sum.mul_prec(&sum, wp)?;
+        }
+        Ok(sum.with_prec(self.prec))
+    }
+
+    // Natural log for positive numbers
+    pub fn ln(&self) -> Option<Self> {
+        if self.is_negative() || self.is_zero() {
+            return None;
+        }
+
+        // x = m * 10^e with m in [1, 10), so ln x = ln m + e*ln 10
+        let e = self.point() - 1;
+        let wp = self.prec + GUARD_DIGITS + num_digits(&BigInt::from(e));
+        let m = Self::new(self.mant.clone(), self.exp - e, wp);
+        let mut res = ln_newton(&m, wp)?;
+        if e != 0 {
+            let ln10 = ln_newton(&Self::from_i64(10, wp), wp)?;
+            res = res.add_prec(&ln10.mul_prec(&Self::from_i64(e, wp), wp).ok()?, wp);
+        }
+        Some(res.with_prec(self.prec))
+    }
+
+    pub fn pi(prec: u32) -> Self {
+        // Machin's formula: pi = 16 atan(1/5) - 4 atan(1/239)
+        let wp = prec + GUARD_DIGITS;
+        let unity = pow10(wp);
+        let pi = (atan_inv(5, &unity) * 16) - (atan_inv(239, &unity) * 4);
+        Self::new(pi, -(wp as i64), prec)
+    }
+
+    pub fn e(prec: u32) -> Self {
+        Self::one(prec).exp().expect("e fits in a decimal")
+    }
+
+    // Like the other functions, None means the caller falls back to a float, here because x
+    // is too big or small to work with as a decimal
+    pub fn sin(&self) -> Option<Self> {
+        self.sin_cos(true)
+    }
+
+    pub fn cos(&self) -> Option<Self> {
+        self.sin_cos(false)
+    }
+
+    // Shared Taylor series for sin and cos after pulling x into [-pi, pi]
+    fn sin_cos(&self, is_sin: bool) -> Option<Self> {
+        let wp = self.prec + GUARD_DIGITS + u32::try_from(self.point().max(0)).ok()?;
+        let two_pi = Self::pi(wp).mul_prec(&Self::from_i64(2, wp), wp).ok()?;
+        let turns = self.div_prec(&two_pi, wp).ok()?.round_int();
+        let x = self.with_prec(wp).add_prec(
+            &-two_pi.mul_prec(&Self::from_int(&turns, wp), wp).ok()?, wp
+        );
+        let x_sq = x.mul_prec(&x, wp).ok()?;
+
+        let (mut sum, mut n) = if is_sin { (x.clone(), 1) } else { (Self::one(wp), 0) };
+        let mut term = sum.clone();
+        loop {
+            // Next term is -term * x^2 / ((n + 1)(n + 2))
+            term = -term.mul_prec(&x_sq, wp).ok()?.div_i64((n + 1) * (n + 2), wp).ok()?;
+            if term.is_zero() || term.point() < -(wp as i64) {
+                break;
+            }
+            sum = sum.add_prec(&term, wp);
+            n += 2;
+        }
+        Some(sum.with_prec(self.prec))
+    }
+
+    pub fn sqrt(&self) -> Option<Self> {
+        if self.is_negative() {
+            return None;
+        }
+
+        // Make the exponent even and give the integer root enough digits to work with
+        let wp = self.prec + GUARD_DIGITS;
+        let mut shift = (2 * wp as i64 - num_digits(&self.mant) as i64).max(0);
+        if (self.exp - shift) % 2 != 0 {
+            shift += 1;
+        }
+        let root = (&self.mant * pow10(shift as u32)).sqrt();
+        Some(Self::new(root, (self.exp - shift) / 2, self.prec))
+    }
+
+    pub fn atan(&self) -> Option<Self> {
+        let wp = self.prec + GUARD_DIGITS;
+        let one = Self::one(wp);
+
+        // atan(x) = +/-pi/2 - atan(1/x) for |x| > 1
+        if self.abs() > one {
+            let half_pi = Self::pi(wp).div_i64(2, wp).ok()?;
+            let inner = one.div_prec(self, wp).ok()?.atan_small(wp)?;
+            let res = if self.is_negative() { -half_pi } else { half_pi };
+            return Some(res.add_prec(&-inner, wp).with_prec(self.prec));
+        }
+        Some(self.with_prec(wp).atan_small(wp)?.with_prec(self.prec))
+    }
+
+    // Series for |x| <= 1, after shrinking x with atan(x) = 2 atan(x / (1 + sqrt(1 + x^2)))
+    fn atan_small(&self, wp: u32) -> Option<Self> {
+        let one = Self::one(wp);
+        let tenth = Self::parse("0.1", wp).unwrap();
+        let mut x = self.clone();
+        let mut doublings = 0;
+        while x.abs() > tenth {
+            let root = one.add_prec(&x.mul_prec(&x, wp).ok()?, wp).sqrt().unwrap();
+            x = x.div_prec(&one.add_prec(&root, wp), wp).ok()?;
+            doublings += 1;
+        }
+
+        let x_sq = x.mul_prec(&x, wp).ok()?;
+        let mut sum = x.clone();
+        let mut power = x;
+        let mut n = 1;
+        loop {
+            power = -power.mul_prec(&x_sq, wp).ok()?;
+            let term = power.div_i64(2 * n + 1, wp).ok()?;
+            if term.is_zero() || term.point() < -(wp as i64) {
+                break;
+            }
+            sum = sum.add_prec(&term, wp);
+            n += 1;
+        }
+        sum.mul_prec(&Self::from_int(&(BigInt::one() << doublings), wp), wp).ok()
+    }
+
+    // Real only, so None outside [-1, 1]
+    pub fn asin(&self) -> Option<Self> {
+        let wp = self.prec + GUARD_DIGITS;
+        let one = Self::one(wp);
+        match self.abs().cmp(&one) {
+            Ordering::Greater => None,
+            Ordering::Equal => {
+                let half_pi = Self::pi(self.prec).div_i64(2, self.prec).ok()?;
+                Some(if self.is_negative() { -half_pi } else { half_pi })
+            }, Ordering::Less => {
+                // asin(x) = atan(x / sqrt(1 - x^2))
+                let x = self.with_prec(wp);
+                let root = one.add_prec(&-x.mul_prec(&x, wp).ok()?, wp).sqrt()?;
+                Some(x.div_prec(&root, wp).ok()?.atan()?.with_prec(self.prec))
+            }
+        }
+    }
+
+    pub fn acos(&self) -> Option<Self> {
+        let wp = self.prec + GUARD_DIGITS;
+        let asin = self.with_prec(wp).asin()?;
+        Some(Self::pi(wp).div_i64(2, wp).ok()?.add_prec(&-asin, wp).with_prec(self.prec))
+    }
+
+    pub fn log10(&self) -> Option<Self> {
+        let wp = self.prec + GUARD_DIGITS;
+        let ln = self.with_prec(wp).ln()?;
+        let ln10 = Self::from_i64(10, wp).ln()?;
+        Some(ln.div_prec(&ln10, wp).ok()?.with_prec(self.prec))
+    }
+
+    pub fn floor(&self) -> Self {
+        if self.is_integer() {
+            return self.clone();
+        }
+        // All of the digits are after the point, so it's just under 1 away from 0
+        if self.point() <= 0 {
+            return Self::from_i64(if self.is_negative() { -1 } else { 0 }, self.prec);
+        }
+        let frac_digits = u32::try_from(-self.exp).expect("Only the digits after the point are dropped");
+        Self::from_int(&self.mant.div_floor(&pow10(frac_digits)), self.prec)
+    }
+
+    pub fn ceil(&self) -> Self {
+        -(-self.clone()).floor()
+    }
+
+    // Real powers. None when the answer would be complex, and an error when it's too big or small
+    pub fn pow(&self, other: &Self) -> Result<Option<Self>, CalcError> {
+        let prec = self.prec.max(other.prec);
+        // Anything past 19 digits is too big for an i64 anyway, so don't write it out
+        let whole = if other.point() <= 19 { other.to_integer().and_then(|n| n.to_i64()) } else { None };
+        if let Some(n) = whole {
+            return Ok(Some(self.powi(n, prec)?));
+        }
+
+        if self.is_zero() {
+            Ok(Some(Self::zero(prec)))
+        } else if self.is_negative() {
+            Ok(None)
+        } else {
+            let wp = prec + GUARD_DIGITS;
+            match self.with_prec(wp).ln() {
+                None => Ok(None),
+                Some(ln) => Ok(Some(ln.mul_prec(other, wp)?.exp()?.with_prec(prec)))
+            }
+        }
+    }
+
+    fn powi(&self, n: i64, prec: u32) -> Result<Self, CalcError> {
+        let wp = prec + GUARD_DIGITS + num_digits(&BigInt::from(n));
+        let mut base = self.with_prec(wp);
+        let mut result = Self::one(wp);
+        let mut k = n.unsigned_abs();
+        while k > 0 {
+            if k & 1 == 1 {
+                result = result.mul_prec(&base, wp)?;
+            }
+            // The last squaring isn't needed, and could overflow when the result doesn't
+            if k > 1 {
+                base = base.mul_prec(&base, wp)?;
+            }
+            k >>= 1;
+        }
+        if n < 0 {
+            result = Self::one(wp).div_prec(&result, wp)?;
+        }
+        Ok(result.with_prec(prec))
+    }
+
+    // Nearest integer, halves away from zero
+    fn round_int(&self) -> BigInt {
+        if self.exp >= 0 {
+            return self.to_integer().unwrap();
+        }
+        let (q, r) = self.mant.abs().div_rem(&pow10((-self.exp) as u32));
+        let q = if r * 2 >= pow10((-self.exp) as u32) { q + 1 } else { q };
+        if self.is_negative() { -q } else { q }
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        let digits = self.mant.abs().to_string();
+        let sign = if self.is_negative() { "-" } else { "" };
+        let point = self.point();
+        if point > (self.prec as i64).max(21) || point < -6 {
+            // Scientific notation when it's too long to write out
+            let (first, rest) = digits.split_at(1);
+            let rest = if rest.is_empty() { String::new() } else { format!(".{}", rest) };
+            write!(f, "{}{}{}e{}", sign, first, rest, point - 1)
+        } else if self.exp >= 0 {
+            write!(f, "{}{}{}", sign, digits, "0".repeat(self.exp as usize))
+        } else if point > 0 {
+            let (whole, frac) = digits.split_at(point as usize);
+            write!(f, "{}{}.{}", sign, whole, frac)
+        } else {
+            write!(f, "{}0.{}{}", sign, "0".repeat((-point) as usize), digits)
+        }
+    }
+}
+
+impl Add for Decimal {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        let prec = self.prec.max(other.prec);
+        self.add_prec(&other, prec)
+    }
+}
+
+impl Sub for Decimal {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        let prec = self.prec.max(other.prec);
+        self.add_prec(&-other, prec)
+    }
+}
+
+// These two can fail if the result's exponent is too big or small to hold
+impl Mul for Decimal {
+    type Output = Result<Self, CalcError>;
+    fn mul(self, other: Self) -> Self::Output {
+        let prec = self.prec.max(other.prec);
+        self.mul_prec(&other, prec)
+    }
+}
+
+impl Div for Decimal {
+    type Output = Result<Self, CalcError>;
+    fn div(self, other: Self) -> Self::Output {
+        let prec = self.prec.max(other.prec);
+        self.div_prec(&other, prec)
+    }
+}
+
+impl Neg for Decimal {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self {
+            mant: -self.mant,
+            exp: self.exp,
+            prec: self.prec
+        }
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Exact, no matter the precisions
+// Signs and sizes settle most of it, so mantissas only get lined up when the points match
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let sign = self.mant.signum().cmp(&other.mant.signum());
+        if sign != Ordering::Equal || self.is_zero() {
+            return sign;
+        }
+
+        let size = match self.point().cmp(&other.point()) {
+            Ordering::Equal => {
+                let exp = self.exp.min(other.exp);
+                shift10(&self.mant.abs(), self.exp - exp).cmp(&shift10(&other.mant.abs(), other.exp - exp))
+            }, size => size
+        };
+        if self.is_negative() { size.reverse() } else { size }
+    }
+}
+
+/* Helpers */
+
+fn out_of_range() -> CalcError {
+    CalcError::Domain(format!("The result's exponent is past the largest a decimal can have (10^{})", MAX_EXP))
+}
+
+fn pow10(n: u32) -> BigInt {
+    BigInt::from(10).pow(n)
+}
+
+// mant * 10^n, for lining up mantissas. Callers make sure n is no bigger than the digits involved
+fn shift10(mant: &BigInt, n: i64) -> BigInt {
+    mant * pow10(u32::try_from(n).expect("Exponent gap too large to line up"))
+}
+
+fn num_digits(int: &BigInt) -> u32 {
+    if int.is_zero() {
+        1
+    } else {
+        int.abs().to_string().len() as u32
+    }
+}
+
+// Round to prec significant digits (halves away from zero) and strip trailing zeros
+fn round_digits(mant: BigInt, exp: i64, prec: u32) -> (BigInt, i64) {
+    if mant.is_zero() {
+        return (mant, 0);
+    }
+
+    let (mut mant, mut exp) = (mant, exp);
+    let digits = num_digits(&mant);
+    if digits > prec {
+        let drop = digits - prec;
+        let divisor = pow10(drop);
+        let (q, r) = mant.abs().div_rem(&divisor);
+        let q = if r * 2 >= divisor { q + 1 } else { q };
+        mant = if mant.is_negative() { -q } else { q };
+        exp += drop as i64;
+    }
+
+    let ten = BigInt::from(10);
+    loop {
+        let (q, r) = mant.div_rem(&ten);
+        if !r.is_zero() {
+            break;
+        }
+        mant = q;
+        exp += 1;
+    }
+    (mant, exp)
+}
+
+// Solve e^y = x with Halley's method, y' = y + 2(x - e^y)/(x + e^y), starting from the f64 answer
+fn ln_newton(x: &Decimal, wp: u32) -> Option<Decimal> {
+    let mut y = Decimal::from_f64(x.to_f64().ln(), wp);
+    let two = Decimal::from_i64(2, wp);
+    for _ in 0..32 {
+        let ey = y.exp().ok()?;
+        let step = two.mul_prec(&x.add_prec(&-ey.clone(), wp), wp).ok()?
+            .div_prec(&x.add_prec(&ey, wp), wp).ok()?;
+        y = y.add_prec(&step, wp);
+        if step.is_zero() || step.point() < y.point() - wp as i64 + 2 {
+            break;
+        }
+    }
+    Some(y)
+}
+
+// atan(1/n) scaled by unity, for Machin's formula
+fn atan_inv(n: i64, unity: &BigInt) -> BigInt {
+    let n_sq = BigInt::from(n * n);
+    let mut power = unity / n;
+    let mut sum = power.clone();
+    let mut k = 1;
+    while !power.is_zero() {
+        power /= &n_sq;
+        let term = &power / (2 * k + 1);
+        if k % 2 == 1 {
+            sum -= term;
+        } else {
+            sum += term;
+        }
+        k += 1;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+    use super::{
+        Decimal, MAX_EXP, out_of_range
+    };
+
+    fn dec(text: &str) -> Decimal {
+        Decimal::parse(text, 30).unwrap()
+    }
+
+    #[test]
+    fn exponent_gaps_past_u32() {
+        // These used to wrap the gap around to 1 and come out as 11 and true
+        assert_eq!((dec("1e4294967297") + dec("1")).to_string(), dec("1e4294967297").to_string());
+        assert_ne!(dec("1e4294967296"), dec("1"));
+        assert_eq!(dec("1e4294967296").cmp(&dec("1")), Ordering::Greater);
+        assert_eq!(dec("-1e4294967296").cmp(&dec("1e-4294967296")), Ordering::Less);
+        assert_eq!(dec("0") - dec("1.5e-4294967296"), dec("-1.5e-4294967296"));
+
+        // Same for exponents cut down to a u32 to make a power of 10
+        assert_eq!(dec("1e4294967297").to_integer(), None);
+        assert_eq!(dec("2").pow(&dec("1e4294967297")).map(|_| ()), Err(out_of_range()));
+        assert_eq!(dec("1.5e-4294967296").floor(), dec("0"));
+        assert_eq!(dec("-1.5e-4294967296").floor(), dec("-1"));
+        assert_eq!(dec("1.5e-5000000000").ceil(), dec("1"));
+        assert_eq!(dec("-25e-1").floor(), dec("-3"));
+
+        // And exponents that won't fit at all are an error rather than an overflow
+        let max = format!("1e{}", MAX_EXP);
+        assert_eq!(Decimal::parse("1e-9223372036854775807", 30), None);
+        assert_eq!(dec(&max) * dec("10"), Err(out_of_range()));
+        assert_eq!(dec(&format!("-{}", max)) / dec("1e-1"), Err(out_of_range()));
+        assert_eq!(dec(&max) * dec("1e-1"), Ok(dec(&format!("1e{}", MAX_EXP - 1))));
+        assert_eq!(Decimal::e(30).pow(&dec("1e20")), Err(out_of_range()));
+        assert_eq!(dec("10").pow(&dec("-2e18")), Err(out_of_range()));
+    }
+
+    #[test]
+    fn large_gaps() {
+        // Too far apart to affect the kept digits, so this has to be quick rather than exact
+        assert_eq!(dec("1e1000000000") + dec("1"), dec("1e1000000000"));
+        assert_eq!(dec("1") - dec("1e-1000000000"), dec("1"));
+
+        // Close enough to still count
+        assert_eq!((dec("1e20") + dec("1")).to_string(), "100000000000000000001");
+        assert_eq!(dec("2.5").cmp(&dec("25e-1")), Ordering::Equal);
+        assert_eq!(dec("-3").cmp(&dec("-20")), Ordering::Greater);
+    }
+}
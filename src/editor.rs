@@ -0,0 +1,316 @@
+/*
+ * Author: Dylan Turner
+ * Description:
+ * - Line editor for the repl on top of termion's raw mode
+ * - Cursor movement, deletion, word jumps, up/down history, Ctrl-R search and Tab completion
+ * - History is kept in a file between sessions
+ * - The line gets colored as it's typed, unless output isn't going to a terminal
+ */
+
+use std::{
+    fs::{
+        read_to_string, write, create_dir_all
+    }, path::PathBuf,
+    io::{
+        stdin, stdout, Write, Stdout
+    }
+};
+use termion::{
+    input::TermRead,
+    event::Key,
+    raw::{
+        IntoRawMode, RawTerminal
+    }, clear::CurrentLine,
+    is_tty,
+    cursor::Left,
+    style::{
+        Faint, Reset
+    }
+};
+use crate::complete::{
+    Candidate, LineHelper
+};
+
+// State for Ctrl-R
+struct Search {
+    query: String,
+    found: Option<usize>, // Index into history of the current match
+    saved: Vec<char> // The line from before searching, for if it gets cancelled
+}
+
+pub struct Editor {
+    history: Vec<String>,
+    history_file: Option<PathBuf>,
+    search: Option<Search>,
+    buffer: Vec<char>,
+    cursor: usize, // In chars, not bytes
+    hist_pos: usize, // history.len() means the line being typed
+    draft: Vec<char>, // What was being typed before going back through history
+    hint: Option<String>, // Shown faintly after the cursor until the next key, e.g. function args
+    color: bool
+}
+
+impl Editor {
+    // Load up history from the file if there is one
+    pub fn new(history_file: Option<PathBuf>) -> Self {
+        let history = history_file.as_ref()
+            .and_then(|path| read_to_string(path).ok())
+            .map(|text| text.lines().map(String::from).collect())
+            .unwrap_or_default();
+        Self {
+            history,
+            history_file,
+            search: None,
+            buffer: Vec::new(),
+            cursor: 0,
+            hist_pos: 0,
+            draft: Vec::new(),
+            hint: None,
+            color: is_tty(&stdout())
+        }
+    }
+
+    // Read one line with editing. None means the user wants to quit, i.e. Ctrl-C or Ctrl-D on an empty line
+    pub fn read_line(&mut self, prompt: &str, helper: &dyn LineHelper) -> Option<String> {
+        let mut out = stdout().into_raw_mode().expect("Could not put the terminal in raw mode");
+        self.buffer.clear();
+        self.cursor = 0;
+        self.hist_pos = self.history.len();
+        self.redraw(&mut out, prompt, helper);
+
+        for key in stdin().keys() {
+            let key = key.expect("Could not read from stdin");
+            self.hint = None;
+            if self.search.is_some() {
+                if self.search_key(key) {
+                    break;
+                }
+                self.redraw(&mut out, prompt, helper);
+                continue;
+            }
+
+            match key {
+                Key::Char('\n') | Key::Char('\r') => break,
+                Key::Ctrl('c') => {
+                    write!(out, "\r\n").unwrap();
+                    return None;
+                }, Key::Ctrl('d') if self.buffer.is_empty() => {
+                    write!(out, "\r\n").unwrap();
+                    return None;
+                }, Key::Char('\t') => self.complete(&mut out, helper),
+                Key::Char(c) => {
+                    self.buffer.insert(self.cursor, c);
+                    self.cursor += 1;
+                }, Key::Backspace | Key::Ctrl('h') if self.cursor > 0 => {
+                    self.cursor -= 1;
+                    self.buffer.remove(self.cursor);
+                }, Key::Delete | Key::Ctrl('d') if self.cursor < self.buffer.len() => {
+                    self.buffer.remove(self.cursor);
+                }, Key::Left | Key::Ctrl('b') => self.cursor = self.cursor.saturating_sub(1),
+                Key::Right | Key::Ctrl('f') => self.cursor = (self.cursor + 1).min(self.buffer.len()),
+                Key::Home | Key::Ctrl('a') => self.cursor = 0,
+                Key::End | Key::Ctrl('e') => self.cursor = self.buffer.len(),
+                Key::Alt('b') => self.cursor = self.word_start(),
+                Key::Alt('f') => self.cursor = self.word_end(),
+                Key::Ctrl('w') => {
+                    let start = self.word_start();
+                    self.buffer.drain(start..self.cursor);
+                    self.cursor = start;
+                }, Key::Alt('d') => {
+                    let end = self.word_end();
+                    self.buffer.drain(self.cursor..end);
+                }, Key::Ctrl('u') => {
+                    self.buffer.drain(..self.cursor);
+                    self.cursor = 0;
+                }, Key::Ctrl('k') => self.buffer.truncate(self.cursor),
+                Key::Ctrl('r') => {
+                    self.draft = self.buffer.clone();
+                    self.search = Some(Search {
+                        query: String::new(),
+                        found: None,
+                        saved: self.buffer.clone()
+                    });
+                },
+                Key::Up | Key::Ctrl('p') if self.hist_pos > 0 => {
+                    if self.hist_pos == self.history.len() {
+                        self.draft = self.buffer.clone();
+                    }
+                    self.hist_pos -= 1;
+                    self.buffer = self.history[self.hist_pos].chars().collect();
+                    self.cursor = self.buffer.len();
+                }, Key::Down | Key::Ctrl('n') if self.hist_pos < self.history.len() => {
+                    self.hist_pos += 1;
+                    self.buffer = if self.hist_pos == self.history.len() {
+                        self.draft.clone()
+                    } else {
+                        self.history[self.hist_pos].chars().collect()
+                    };
+                    self.cursor = self.buffer.len();
+                }, _ => {}
+            }
+            self.redraw(&mut out, prompt, helper);
+        }
+
+        self.hint = None;
+        self.redraw(&mut out, prompt, helper);
+        write!(out, "\r\n").unwrap();
+        Some(self.buffer.iter().collect())
+    }
+
+    // Fill in as much of the word at the cursor as is certain, listing the options if it's unclear
+    fn complete(&mut self, out: &mut RawTerminal<Stdout>, helper: &dyn LineHelper) {
+        let (start, cands) = helper.complete(&self.buffer, self.cursor);
+        let insert = |editor: &mut Self, word: &str| {
+            editor.buffer.splice(start..editor.cursor, word.chars());
+            editor.cursor = start + word.chars().count();
+        };
+
+        match cands.as_slice() {
+            [] => {},
+            [ Candidate { word, args: None } ] => insert(self, word),
+            [ Candidate { word, args: Some(args) } ] => {
+                insert(self, format!("{}(", word).as_str());
+                self.hint = Some(format!("{})", args));
+            }, cands => {
+                let common = cands.iter().skip(1).fold(cands[0].word.clone(), |common, cand| {
+                    common.chars().zip(cand.word.chars())
+                        .take_while(|(a, b)| a == b).map(|(a, _)| a).collect()
+                });
+                if common.chars().count() > self.cursor - start {
+                    insert(self, common.as_str());
+                } else {
+                    // Nothing more to fill in, so show what it could be below the line
+                    let options = cands.iter().map(|cand| match &cand.args {
+                        None => cand.word.clone(),
+                        Some(args) => format!("{}({})", cand.word, args)
+                    }).collect::<Vec<String>>();
+                    write!(out, "\r\n{}\r\n", options.join("  ")).unwrap();
+                }
+            }
+        }
+    }
+
+    // Remember a line for up/down and the history file, keeping at most max_size lines
+    // Blank lines aren't worth keeping, and repeats just move to the end
+    pub fn add_history(&mut self, line: &str, max_size: usize) {
+        if line.trim().is_empty() {
+            return;
+        }
+        self.history.retain(|old| old != line);
+        self.history.push(String::from(line));
+        if self.history.len() > max_size {
+            self.history.drain(..self.history.len() - max_size);
+        }
+
+        if let Some(path) = &self.history_file {
+            let mut text = self.history.join("\n");
+            text.push('\n');
+            // Losing history isn't worth interrupting anyone over
+            if let Some(dir) = path.parent() {
+                let _ = create_dir_all(dir);
+            }
+            let _ = write(path, text);
+        }
+    }
+
+    // Handle a key while in Ctrl-R mode. Gives back true if the line should be submitted
+    fn search_key(&mut self, key: Key) -> bool {
+        let search = self.search.as_mut().unwrap();
+        match key {
+            Key::Char('\n') | Key::Char('\r') => {
+                self.search = None;
+                return true;
+            }, Key::Ctrl('r') => {
+                // Look for an older match
+                let from = search.found.unwrap_or(self.history.len());
+                if let Some(found) = search_back(&self.history, &search.query, from) {
+                    search.found = Some(found);
+                }
+            }, Key::Char(c) => {
+                search.query.push(c);
+                let from = search.found.map(|found| found + 1).unwrap_or(self.history.len());
+                search.found = search_back(&self.history, &search.query, from);
+            }, Key::Backspace => {
+                search.query.pop();
+                search.found = search_back(&self.history, &search.query, self.history.len());
+            }, Key::Ctrl('g') | Key::Ctrl('c') | Key::Esc => {
+                // Cancel and go back to what was there before
+                self.buffer = search.saved.clone();
+                self.cursor = self.buffer.len();
+                self.search = None;
+                return false;
+            }, _ => {
+                // Anything else keeps the match and goes back to normal editing
+                self.search = None;
+                return false;
+            }
+        }
+
+        let search = self.search.as_ref().unwrap();
+        self.buffer = match search.found {
+            Some(found) => self.history[found].chars().collect(),
+            None => search.saved.clone()
+        };
+        self.cursor = self.buffer.len();
+        self.hist_pos = search.found.unwrap_or(self.history.len());
+        false
+    }
+
+    // Rewrite the whole line and put the cursor back where it belongs
+    fn redraw(&self, out: &mut RawTerminal<Stdout>, prompt: &str, helper: &dyn LineHelper) {
+        let line = if self.color {
+            helper.highlight(&self.buffer, self.cursor)
+        } else {
+            self.buffer.iter().collect::<String>()
+        };
+        let prompt = match &self.search {
+            None => String::from(prompt),
+            Some(Search { query, found: None, .. }) if !query.is_empty() =>
+                format!("(failed reverse-i-search)`{}': ", query),
+            Some(Search { query, .. }) => format!("(reverse-i-search)`{}': ", query)
+        };
+        let hint = match &self.hint {
+            None => String::new(),
+            Some(hint) => format!("{}{}{}", Faint, hint, Reset)
+        };
+        write!(out, "\r{}{}{}{}", CurrentLine, prompt, line, hint).unwrap();
+        let after = self.buffer.len() - self.cursor
+            + self.hint.as_ref().map(|hint| hint.chars().count()).unwrap_or(0);
+        if after > 0 {
+            write!(out, "{}", Left(after as u16)).unwrap();
+        }
+        out.flush().unwrap();
+    }
+
+    // Words are runs of letters, digits and '_', like identifiers
+    fn word_start(&self) -> usize {
+        let mut pos = self.cursor;
+        while pos > 0 && !is_word_char(self.buffer[pos - 1]) {
+            pos -= 1;
+        }
+        while pos > 0 && is_word_char(self.buffer[pos - 1]) {
+            pos -= 1;
+        }
+        pos
+    }
+
+    fn word_end(&self) -> usize {
+        let mut pos = self.cursor;
+        while pos < self.buffer.len() && !is_word_char(self.buffer[pos]) {
+            pos += 1;
+        }
+        while pos < self.buffer.len() && is_word_char(self.buffer[pos]) {
+            pos += 1;
+        }
+        pos
+    }
+}
+
+// Newest line before 'from' that contains the query
+fn search_back(history: &[String], query: &str, from: usize) -> Option<usize> {
+    (0..from.min(history.len())).rev().find(|&i| history[i].contains(query))
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
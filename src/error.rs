@@ -0,0 +1,166 @@
+/*
+ * Author: Dylan Turner
+ * Description:
+ * - Error types shared between the parser and the interpreter
+ * - ParseError knows where in the source it happened so it can point at the problem
+ */
+
+use std::fmt::{
+    Display, Formatter, Result as FmtResult
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseErrorKind {
+    UnexpectedToken(String),
+    UnexpectedEnd,
+    ExtraCharacters(String),
+    UnclosedDelimiter(char),
+    InvalidLiteral(String)
+}
+
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: (usize, usize), // Byte offsets into the source, end exclusive
+    pub expected: Vec<String>
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, span: (usize, usize), expected: &[&str]) -> Self {
+        Self {
+            kind,
+            span,
+            expected: expected.iter().map(|exp| String::from(*exp)).collect()
+        }
+    }
+
+    pub fn message(&self) -> String {
+        let expected = match self.expected.len() {
+            0 => String::new(),
+            1 => self.expected[0].clone(),
+            n => format!(
+                "{} or {}", self.expected[..n - 1].join(", "), self.expected[n - 1]
+            )
+        };
+        match &self.kind {
+            ParseErrorKind::UnexpectedToken(found) if expected.is_empty() =>
+                format!("Unexpected '{}'", found),
+            ParseErrorKind::UnexpectedToken(found) =>
+                format!("Expected {} but found '{}'", expected, found),
+            ParseErrorKind::UnexpectedEnd if expected.is_empty() =>
+                String::from("Unexpected end of input"),
+            ParseErrorKind::UnexpectedEnd =>
+                format!("Expected {} but reached end of input", expected),
+            ParseErrorKind::ExtraCharacters(found) =>
+                format!("Extra characters '{}' at end of statement", found),
+            ParseErrorKind::UnclosedDelimiter(open) =>
+                format!("Unclosed '{}', expected {}", open, expected),
+            ParseErrorKind::InvalidLiteral(text) =>
+                format!("Invalid literal '{}'", text)
+        }
+    }
+
+    // Where in src the error is. Running out of input after a trailing newline points at the end
+    // of the last line with anything on it, rather than the empty line after it
+    pub fn position(&self, src: &str) -> usize {
+        let end = src.trim_end().len();
+        if self.span.0 >= end { self.span.0.min(end) } else { self.span.0 }
+    }
+
+    // Show the line the error is on with the offending span underlined like:
+    //   let x := (1 + 2
+    //                   ^
+    pub fn render(&self, src: &str) -> String {
+        let start = self.position(src);
+        let line_start = src[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = src[start..].find('\n').map(|i| start + i).unwrap_or(src.len());
+        let line = &src[line_start..line_end];
+
+        let col = src[line_start..start].chars().count();
+        let end = self.span.1.clamp(start, line_end);
+        let width = src[start..end].chars().count().max(1);
+
+        let mut underline = " ".repeat(col);
+        underline.push('^');
+        underline.push_str("~".repeat(width - 1).as_str());
+
+        format!("Error: {}\n  {}\n  {}", self.message(), line, underline)
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{} at pos {}", self.message(), self.span.0)
+    }
+}
+
+// Everything that can go wrong while evaluating an already parsed statement
+#[derive(Clone, Debug, PartialEq)]
+pub enum CalcError {
+    UnknownVariable(String),
+    UnknownFunction(String),
+    Arity { func: String, expected: usize, found: usize },
+    TypeMismatch(String),
+    Domain(String),
+    Plugin(String),
+    DivisionByZero,
+    InvalidCommand(String)
+}
+
+impl Display for CalcError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            CalcError::UnknownVariable(name) => write!(f, "No such variable '{}'", name),
+            CalcError::UnknownFunction(name) => write!(f, "No such function '{}'", name),
+            CalcError::Arity { func, expected, found } => write!(
+                f, "'{}' expects {} argument{} but got {}",
+                func, expected, if *expected == 1 { "" } else { "s" }, found
+            ), CalcError::TypeMismatch(msg) => write!(f, "Type mismatch: {}", msg),
+            CalcError::Domain(msg) => write!(f, "Domain error: {}", msg),
+            CalcError::Plugin(msg) => write!(f, "Plugin failure: {}", msg),
+            CalcError::DivisionByZero => write!(f, "Division by zero"),
+            CalcError::InvalidCommand(msg) => write!(f, "Invalid command: {}", msg)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ParseError, ParseErrorKind
+    };
+    use crate::parser::parse_stmt;
+
+    fn render(src: &str) -> String {
+        parse_stmt(src).unwrap_err().render(src)
+    }
+
+    #[test]
+    fn carets() {
+        assert_eq!(render("1 + )"), "Error: Expected a number, an identifier, '(' or '[' but found ')'\n  1 + )\n      ^");
+        assert_eq!(render("1 2"), "Error: Extra characters '2' at end of statement\n  1 2\n    ^");
+        assert_eq!(render("  0x_ + 1"), "Error: Invalid literal '0x_'\n    0x_ + 1\n    ^~~");
+        assert_eq!(render("let x := (1 + 2"), "Error: Unclosed '(', expected ')'\n  let x := (1 + 2\n                 ^");
+    }
+
+    // Only the line with the error is shown, and the column counts chars rather than bytes
+    #[test]
+    fn multiple_lines() {
+        assert_eq!(render("(1 +\n2 ) )"), "Error: Extra characters ')' at end of statement\n  2 ) )\n      ^");
+        let err = ParseError::new(ParseErrorKind::UnexpectedToken(String::from(")")), (9, 10), &[]);
+        assert_eq!(err.render("1 +\nπ + )"), "Error: Unexpected ')'\n  π + )\n      ^");
+    }
+
+    // Running out of input after a newline points after the last thing typed, not at an empty line
+    #[test]
+    fn end_of_input() {
+        let expected = "Error: Expected a number, an identifier, '(' or '[' but reached end of input";
+        assert_eq!(render("foo("), format!("{}\n  foo(\n      ^", expected));
+        assert_eq!(render("foo(\n"), format!("{}\n  foo(\n      ^", expected));
+        assert_eq!(render("f(1,\n2 +\n\n  "), format!("{}\n  2 +\n     ^", expected));
+
+        let err = parse_stmt("foo(\n").unwrap_err();
+        assert_eq!(err.position("foo(\n"), 4);
+        assert_eq!(parse_stmt("1 + )\n").unwrap_err().position("1 + )\n"), 4);
+    }
+}
@@ -3,17 +3,30 @@
  * Description: Take parser output and evaluate or adjust an environment
  */
 
-use std::collections::HashMap;
+use std::{
+    cell::Cell,
+    collections::HashMap
+};
 use crate::{
     var::Var,
+    error::CalcError,
     parser::Token,
     builtin::BUILTIN_FUNCS,
-    complex::{
+    int::Int,
+    decimal::Decimal,
+    settings::{
+        Settings, FloatMode
+    }, complex::{
         FComplex,
         IComplex
+    }, word::{
+        Flags, word_op, word_neg, wrap_var
     }, plugin::call_ext_fn
 };
 
+// Functions that only change how a result is shown, see eval_in_base
+const BASE_FUNCS: [&str; 4] = [ "hex", "oct", "bin", "base" ];
+
 #[derive(Clone, Debug)]
 pub struct Func {
     pub args: Vec<String>,
@@ -23,77 +36,190 @@ pub struct Func {
 #[derive(Clone, Debug)]
 pub struct Environment {
     pub vars: HashMap<String, Var>,
-    pub funcs: HashMap<String, Func>
+    pub funcs: HashMap<String, Func>,
+    pub settings: Settings,
+    pub ans: Option<Var>, // The last result
+    pub results: Vec<Var>, // The last few results, for _1, _2, etc
+    pub result_count: usize, // How many results there have been, i.e. the number of the newest one
+    pub flags: Cell<Flags> // Carries and overflows in the statement being run, for programmer mode
 }
 
 impl Environment {
     pub fn new() -> Self {
         Self {
             vars: HashMap::new(),
-            funcs: HashMap::new()
+            funcs: HashMap::new(),
+            settings: Settings::new(),
+            ans: None,
+            results: Vec::new(),
+            result_count: 0,
+            flags: Cell::new(Flags::default())
+        }
+    }
+
+    // Look up a name, falling back on 'ans' and numbered results if it isn't a real variable
+    pub fn get_var(&self, name: &str) -> Option<Var> {
+        if let Some(val) = self.vars.get(name) {
+            return Some(val.clone());
+        }
+        if name == "ans" {
+            return self.ans.clone();
+        }
+        let num = name.strip_prefix('_')?.parse::<usize>().ok()?;
+        self.numbered_results().find(|(res_num, _)| *res_num == num).map(|(_, val)| val.clone())
+    }
+
+    // Remember a result as 'ans' and the next numbered one
+    pub fn add_result(&mut self, val: Var) {
+        self.ans = Some(val.clone());
+        self.results.push(val);
+        self.result_count += 1;
+        self.trim_results();
+    }
+
+    // Forget the oldest results past what the 'results' setting keeps
+    pub fn trim_results(&mut self) {
+        if self.results.len() > self.settings.result_size {
+            self.results.drain(..self.results.len() - self.settings.result_size);
+        }
+    }
+
+    // Each kept result along with its number, oldest first
+    pub fn numbered_results(&self) -> impl Iterator<Item = (usize, &Var)> {
+        let first = self.result_count - self.results.len() + 1;
+        self.results.iter().enumerate().map(move |(i, val)| (first + i, val))
+    }
+}
+
+// What a successfully evaluated statement did, so callers can decide how to report it
+#[derive(Clone, Debug)]
+pub enum Outcome {
+    Value(Var),
+    InBase(Var, u32), // From hex(x) and the like, which just change how the value is shown
+    Flagged(Box<Outcome>, Flags), // A value or assignment in programmer mode, with its carry/overflow flags
+    FunctionDefined { name: String, args: Vec<String>, updated: bool },
+    VarAssigned { name: String, value: Var, updated: bool }
+}
+
+impl Outcome {
+    // Values are written out the way the display settings say
+    pub fn to_string(&self, settings: &Settings) -> String {
+        match self {
+            Outcome::Value(val) => val.to_string(settings),
+            Outcome::InBase(val, base) => val.to_string(&Settings { base: *base, ..settings.clone() }),
+            Outcome::Flagged(inner, flags) => match flags.names() {
+                names if names.is_empty() => inner.to_string(settings),
+                names => format!("{} [{}]", inner.to_string(settings), names.join(", "))
+            },
+            Outcome::FunctionDefined { name, args, updated: false } => format!(
+                "Added function '{}' with args {:?} to environment.", name, args
+            ), Outcome::FunctionDefined { name, updated: true, .. } => format!(
+                "Updated function '{}'", name
+            ), Outcome::VarAssigned { name, value, updated: false } => format!(
+                "Added var '{}' with value {} to environment.", name, value.to_string(settings)
+            ), Outcome::VarAssigned { name, value, updated: true } => format!(
+                "Updated var '{}' to {}", name, value.to_string(settings)
+            )
         }
     }
 }
 
-pub fn eval(ast: &Token, env: &mut Environment) -> String {
-    if let Token::Statement(stmt) = ast {
-        match stmt.as_ref() {
-            Token::FunctionDefinition(name, args, sub_expr) => eval_func_def(
-                name, args, sub_expr, env
-            ), Token::Assignment(name, sub_expr) => eval_asgn(name, sub_expr, env),
-            _ => match eval_expr(stmt, env) {
-                Err(err) => format!("Error: {}", err),
-                Ok(val) => val.to_string()
+pub fn eval(ast: &Token, env: &mut Environment) -> Result<Outcome, CalcError> {
+    let stmt = match ast {
+        Token::Statement(stmt) => stmt.as_ref(),
+        expr => expr
+    };
+    env.flags.set(Flags::default());
+    let outcome = match stmt {
+        Token::FunctionDefinition(name, args, sub_expr) => return Ok(eval_func_def(
+            name, args, sub_expr, env
+        )), Token::Assignment(name, sub_expr) => eval_asgn(name, sub_expr, env)?,
+        _ => {
+            let (val, base) = match expr_call(stmt) {
+                Some((name, args)) if BASE_FUNCS.contains(&name.as_str()) => {
+                    let (val, base) = eval_in_base(name, args, env)?;
+                    (val, Some(base))
+                }, _ => (eval_expr(stmt, env)?, None)
+            };
+            let val = fit_word(val, env);
+            env.add_result(val.clone());
+            match base {
+                None => Outcome::Value(val),
+                Some(base) => Outcome::InBase(val, base)
             }
         }
-    } else {
-        String::from("Not implemented")
+    };
+
+    // Programmer mode always says how the flags ended up, even if neither got set
+    Ok(match env.settings.word {
+        None => outcome,
+        Some(_) => Outcome::Flagged(Box::new(outcome), env.flags.get())
+    })
+}
+
+// hex(x), base(x, b), etc. Gives back x and the base to show it in
+fn eval_in_base(name: &str, args: &[Box<Token>], env: &Environment) -> Result<(Var, u32), CalcError> {
+    let expected = if name == "base" { 2 } else { 1 };
+    if args.len() != expected {
+        return Err(CalcError::Arity { func: String::from(name), expected, found: args.len() });
+    }
+
+    let val = eval_expr(&args[0], env)?;
+    if !only_ints(&val) {
+        return Err(CalcError::TypeMismatch(format!("'{}' only works on ints and fractions", name)));
+    }
+    let base = match name {
+        "hex" => 16,
+        "oct" => 8,
+        "bin" => 2,
+        _ => match eval_expr(&args[1], env)? {
+            Var::Int(int) if int.is_real() => match int.re.to_i64() {
+                Some(base) if (2..=36).contains(&base) => base as u32,
+                _ => return Err(CalcError::Domain(format!("There's no base {} to show numbers in", int.re)))
+            }, _ => return Err(CalcError::TypeMismatch(String::from("'base' needs an int base from 2 to 36")))
+        }
+    };
+    Ok((val, base))
+}
+
+// Whether something has nothing but exact values in it, so it can be written in another base
+fn only_ints(var: &Var) -> bool {
+    match var {
+        Var::Int(_) | Var::Rational(_) => true,
+        Var::Float(_) | Var::Decimal(_) => false,
+        Var::List(items) => items.iter().all(only_ints)
     }
 }
 
 // Add a function for later
-fn eval_func_def(
-        name: &String, args: &Vec<String>, expr: &Token, env: &mut Environment) -> String {
-    let succ = env.funcs.insert(
-        name.clone(),
+fn eval_func_def(name: &str, args: &[String], expr: &Token, env: &mut Environment) -> Outcome {
+    let prev = env.funcs.insert(
+        String::from(name),
         Func {
-            args: args.clone(),
+            args: args.to_vec(),
             expr: expr.clone()
         }
     );
-
-    // Newly added
-    if succ.is_none() {
-        format!("Added function '{}' with args {:?} to environment.", name, args)
-    } else {
-        // Updated
-        format!("Updated function '{}'", name)
+    Outcome::FunctionDefined {
+        name: String::from(name),
+        args: args.to_vec(),
+        updated: prev.is_some()
     }
 }
 
 // Add/set an identifier
-fn eval_asgn(name: &String, sub_expr: &Token, env: &mut Environment) -> String {
-    match eval_expr(sub_expr, env) {
-        Err(err) => format!("Error: {}", err),
-        Ok(val) => {
-            let succ = env.vars.insert(name.clone(), val);
-
-            // Newly added
-            if succ.is_none() {
-                format!(
-                    "Added var '{}' with value {} to environment.",
-                    name, env.vars[&name.clone()].to_string()
-                )
-            } else {
-                // Updated
-                format!("Updated var '{}' to {}", name, env.vars[&name.clone()].to_string())
-            }
-        }
-    }
+fn eval_asgn(name: &str, sub_expr: &Token, env: &mut Environment) -> Result<Outcome, CalcError> {
+    let value = fit_word(eval_expr(sub_expr, env)?, env);
+    let prev = env.vars.insert(String::from(name), value.clone());
+    Ok(Outcome::VarAssigned {
+        name: String::from(name),
+        value,
+        updated: prev.is_some()
+    })
 }
 
 // Meat and bones - actually calculate stuff
-fn eval_expr(ast: &Token, env: &Environment) -> Result<Var, String> {
+fn eval_expr(ast: &Token, env: &Environment) -> Result<Var, CalcError> {
     match ast {
         Token::Expression(un, t, f) => {
             if t.is_none() {
@@ -105,7 +231,7 @@ fn eval_expr(ast: &Token, env: &Environment) -> Result<Var, String> {
                 return test;
             }
 
-            if test.unwrap().to_string() == "0" {
+            if test.unwrap().is_zero() {
                 eval_expr(f.clone().unwrap().as_ref(), env)
             } else {
                 eval_expr(t.clone().unwrap().as_ref(), env)
@@ -123,13 +249,13 @@ fn eval_expr(ast: &Token, env: &Environment) -> Result<Var, String> {
                             Ok(val.unwrap().to_lat())
                         }
                     }, "-" => {
-                        let val = eval_expr(exp, env);
-                        if val.is_err() {
-                            val
-                        } else {
-                            Ok(val.unwrap().to_neg())
-                        }
-                    }, _ => Err(String::from("Impossible!"))
+                        let val = eval_expr(exp, env)?;
+                        Ok(match env.settings.word {
+                            None => val.to_neg(),
+                            Some(word) => word_neg(val, word, &env.flags)
+                        })
+                    }, "~" => eval_expr(exp, env)?.to_not().map(|val| fit_word(val, env)),
+                    _ => unreachable!("Unknown unary operator '{}'", op.clone().unwrap())
                 }
             }
         }, Token::ExponentialExpression(left, right) => {
@@ -144,87 +270,45 @@ fn eval_expr(ast: &Token, env: &Environment) -> Result<Var, String> {
                 if right_val.is_err() {
                     return right_val;
                 }
-                Ok(left_val.unwrap() ^ right_val.unwrap())
+                machine(left_val.unwrap(), "^", right_val.unwrap(), env, |a, b| a ^ b)
             }
-        }, Token::ProductExpression(left, op, right) => {
-            if right.is_none() {
-                eval_expr(left, env)
-            } else {
-                let left_val = eval_expr(left, env);
-                if left_val.is_err() {
-                    return left_val;
-                }
-                let right_val = eval_expr(right.clone().unwrap().as_ref(), env);
-                if right_val.is_err() {
-                    return right_val;
-                }
-                match op.clone().unwrap().as_str() {
-                    "*" => Ok(left_val.unwrap() * right_val.unwrap()),
-                    "/" => Ok(left_val.unwrap() / right_val.unwrap()),
-                    _ => Err(String::from("Impossible!"))
-                }
+        }, Token::ProductExpression(first, rest) => eval_chain(first, rest, env, |a, op, b| {
+            match op {
+                "*" => machine(a, op, b, env, |a, b| a * b),
+                "/" => machine(a, op, b, env, |a, b| a / b),
+                _ => unreachable!("Unknown product operator '{}'", op)
             }
-        }, Token::SumExpression(left, op, right) => {
-            if right.is_none() {
-                eval_expr(left, env)
-            } else {
-                let left_val = eval_expr(left, env);
-                if left_val.is_err() {
-                    return left_val;
-                }
-                let right_val = eval_expr(right.clone().unwrap().as_ref(), env);
-                if right_val.is_err() {
-                    return right_val;
-                }
-                match op.clone().unwrap().as_str() {
-                    "+" => Ok(left_val.unwrap() + right_val.unwrap()),
-                    "-" => Ok(left_val.unwrap() - right_val.unwrap()),
-                    _ => Err(String::from("Impossible!"))
-                }
-            }
-        }, Token::RelationalExpression(left, op, right) => {
-            if right.is_none() {
-                eval_expr(left, env)
-            } else {
-                let left_val = eval_expr(left, env);
-                if left_val.is_err() {
-                    return left_val;
-                }
-                let right_val = eval_expr(right.clone().unwrap().as_ref(), env);
-                if right_val.is_err() {
-                    return right_val;
-                }
-                Ok(left_val.unwrap().do_cmp(right_val.unwrap(), op.clone().unwrap().as_str()))
+        }), Token::SumExpression(first, rest) => eval_chain(first, rest, env, |a, op, b| {
+            match op {
+                "+" => machine(a, op, b, env, |a, b| a + b),
+                "-" => machine(a, op, b, env, |a, b| a - b),
+                _ => unreachable!("Unknown sum operator '{}'", op)
             }
-        }, Token::Term(inner) => eval_expr(inner, env),
+        }), Token::RelationalExpression(first, rest) => eval_chain(
+            first, rest, env, |a, op, b| a.do_cmp(b, op)
+        ), Token::ShiftExpression(first, rest)
+            | Token::AndExpression(first, rest)
+            | Token::XorExpression(first, rest)
+            | Token::OrExpression(first, rest) => eval_chain(
+            first, rest, env, |a, op, b| machine(a, op, b, env, |a, b| a.do_bitwise(b, op))
+        ), Token::Term(inner) => eval_expr(inner, env),
         Token::Integer(text) => {
-            match text.split_at(text.len() - 1).0.parse::<i64>() {
-                Err(_) => Err(format!("Failed to parse integer {}", text)),
-                Ok(val) => {
-                    Ok(Var {
-                        ls_data: None,
-                        num_data: None,
-                        int_data: Some(IComplex::new_polar(val, 0))
-                    })
-                }
-            }
-        }, Token::Number(text) => {
-            match text.parse::<f64>() {
-                Err(_) => Err(format!("Failed to parse number {}", text)),
-                Ok(val) => {
-                    Ok(Var {
-                        ls_data: None,
-                        num_data: Some(FComplex::new_polar(val, 0.0)),
-                        int_data: None
-                    })
-                }
+            // Inner '_'s are just for breaking up digits
+            match text.replace('_', "").parse::<Int>() {
+                Err(err) => Err(CalcError::Domain(err)),
+                Ok(val) => Ok(Var::Int(IComplex::new_cardinal(val, Int::from(0))))
             }
-        }, Token::Identifier(name) => {
-            if env.vars.contains_key(name) {
-                Ok(env.vars[&name.clone()].clone())
-            } else {
-                Err(format!("No such variable '{}'", name))
+        }, Token::Number(text) => match env.settings.float_mode {
+            FloatMode::Binary => match text.parse::<f64>() {
+                Err(_) => Err(CalcError::Domain(format!("Failed to parse number {}", text))),
+                Ok(val) => Ok(Var::Float(FComplex::new_polar(val, 0.0)))
+            }, FloatMode::Decimal => match Decimal::parse(text, env.settings.precision) {
+                None => Err(CalcError::Domain(format!("Failed to parse number {}", text))),
+                Some(val) => Ok(Var::Decimal(val))
             }
+        }, Token::Identifier(name) => match env.get_var(name) {
+            None => Err(CalcError::UnknownVariable(name.clone())),
+            Some(val) => Ok(val)
         }, Token::List(items) => {
             let mut var_arr = Vec::new();
             for item in items {
@@ -234,64 +318,20 @@ fn eval_expr(ast: &Token, env: &Environment) -> Result<Var, String> {
                 }
                 var_arr.push(res.unwrap());
             }
-            Ok(Var {
-                ls_data: Some(var_arr),
-                num_data: None,
-                int_data: None
-            })
+            Ok(Var::List(var_arr))
         }, Token::FunctionCall(name, args) => {
             // Try built in funcs first
             if name == "call" {
                 // Special case w/ identifiers (don't eval)
-                if args.len() < 2 {
-                    Err(String::from("Two few arguments to 'call'"))
+                if args.is_empty() {
+                    Err(CalcError::Arity { func: name.clone(), expected: 1, found: 0 })
                 } else {
-                    // First two args should be idents
-                    let lib_str;
-                    if let Token::Expression(u, _, _) = args[0].clone().as_ref() {
-                        if let Token::UnaryExpression(e, _) = u.clone().as_ref() {
-                            if let Token::ExponentialExpression(p, _) = e.clone().as_ref() {
-                                if let Token::ProductExpression(s, _, _) = p.clone().as_ref() {
-                                    if let Token::SumExpression(r, _, _) = s.clone().as_ref() {
-                                        if let Token::RelationalExpression(t, _, _)
-                                                = r.clone().as_ref() {
-                                            if let Token::Term(i) = t.clone().as_ref() {
-                                                if let Token::Identifier(l) = i.clone().as_ref() {
-                                                    lib_str = l.clone();
-                                                } else {
-                                                    return Err(String::from(
-                                                        "Expected ident for 1st arg in 'call'"
-                                                    ));
-                                                }  
-                                            } else {
-                                                return Err(String::from(
-                                                    "Expected ident for 1st arg in 'call'"
-                                                ));
-                                            }
-                                        } else {
-                                            return Err(String::from(
-                                                "Expected ident for 1st arg in 'call'"
-                                            ));
-                                        }
-                                    } else {
-                                        return Err(String::from(
-                                            "Expected ident for 1st arg in 'call'"
-                                        ));
-                                    }
-                                } else {
-                                    return Err(String::from(
-                                        "Expected ident for 1st arg in 'call'"
-                                    ));
-                                }
-                            } else {
-                                return Err(String::from("Expected ident for 1st arg in 'call'"));
-                            }
-                        } else {
-                            return Err(String::from("Expected ident for 1st arg in 'call'"));
-                        }
-                    } else {
-                        return Err(String::from("Expected ident for 1st argument in 'call'"));
-                    }
+                    // First arg should be an ident
+                    let lib_str = match expr_ident(&args[0]) {
+                        None => return Err(CalcError::TypeMismatch(String::from(
+                            "'call' expects a library name as its first argument"
+                        ))), Some(lib_str) => lib_str
+                    };
 
                     let mut real_args = args.clone();
                     real_args.remove(0);
@@ -306,7 +346,11 @@ fn eval_expr(ast: &Token, env: &Environment) -> Result<Var, String> {
 
                     call_ext_fn(lib_str.as_str(), &vars)
                 }
-            } else  if HashMap::from(BUILTIN_FUNCS).contains_key(name.as_str()) {
+            } else if BASE_FUNCS.contains(&name.as_str()) {
+                Err(CalcError::TypeMismatch(format!(
+                    "'{}' only changes how a result is shown, so it has to be the whole expression", name
+                )))
+            } else if HashMap::from(BUILTIN_FUNCS).contains_key(name.as_str()) {
                 let mut eval_args = Vec::new();
                 for arg in args {
                     match eval_expr(arg, env) {
@@ -314,7 +358,7 @@ fn eval_expr(ast: &Token, env: &Environment) -> Result<Var, String> {
                         Ok(val) => eval_args.push(val)
                     }
                 }
-                HashMap::from(BUILTIN_FUNCS)[name.as_str()](&eval_args)
+                HashMap::from(BUILTIN_FUNCS)[name.as_str()](&eval_args, &env.settings)
             } else if env.funcs.contains_key(name) {
                 let mut eval_args = Vec::new();
                 for arg in args {
@@ -323,17 +367,114 @@ fn eval_expr(ast: &Token, env: &Environment) -> Result<Var, String> {
                         Ok(val) => eval_args.push(val)
                     }
                 }
+                let func = &env.funcs[name];
+                if func.args.len() != eval_args.len() {
+                    return Err(CalcError::Arity {
+                        func: name.clone(),
+                        expected: func.args.len(),
+                        found: eval_args.len()
+                    });
+                }
+
                 let mut f_env = env.clone();
-                for i in 0..env.funcs[&name.clone()].args.len() {
-                    f_env.vars.insert(
-                        env.funcs[&name.clone()].args[i].clone(), eval_args[i].clone()
-                    );
+                for (arg, val) in func.args.iter().zip(eval_args) {
+                    f_env.vars.insert(arg.clone(), val);
                 }
-                eval_expr(&env.funcs[&name.clone()].expr, &f_env)
+                let res = eval_expr(&func.expr, &f_env);
+                env.flags.set(f_env.flags.get());
+                res
             } else {
-                Err(format!("No such function '{}'", name))
+                Err(CalcError::UnknownFunction(name.clone()))
             }
-        }, _ => Err(String::from("Impossible!"))
+        }, _ => unreachable!("Can't evaluate {:?} as an expression", ast)
+    }
+}
+
+// In programmer mode ints wrap around to the word size instead of growing, see word_op
+fn machine<F: Fn(Var, Var) -> Result<Var, CalcError> + Copy>(
+        a: Var, op: &str, b: Var, env: &Environment, std_op: F) -> Result<Var, CalcError> {
+    match env.settings.word {
+        None => std_op(a, b),
+        Some(word) => word_op(a, op, b, word, &env.flags, std_op)
+    }
+}
+
+// Whole results and the like just get cut down to fit, without counting as a carry
+fn fit_word(val: Var, env: &Environment) -> Var {
+    match env.settings.word {
+        None => val,
+        Some(word) => wrap_var(val, word)
+    }
+}
+
+// Fold a left-associative operator chain like a + b - c into ((a + b) - c)
+fn eval_chain<F: Fn(Var, &str, Var) -> Result<Var, CalcError>>(
+        first: &Token, rest: &[(String, Box<Token>)], env: &Environment,
+        op_fn: F) -> Result<Var, CalcError> {
+    let mut acc = eval_expr(first, env)?;
+    for (op, operand) in rest {
+        let val = eval_expr(operand, env)?;
+        acc = op_fn(acc, op.as_str(), val)?;
+    }
+    Ok(acc)
+}
+
+// Dig through the precedence layers of an expression to see if it's just a bare identifier
+fn expr_ident(ast: &Token) -> Option<String> {
+    match expr_inner(ast) {
+        Token::Identifier(name) => Some(name.clone()),
+        _ => None
     }
 }
 
+// Same thing for a function call, e.g. hex(x) but not hex(x) + 1
+fn expr_call(ast: &Token) -> Option<(&String, &Vec<Box<Token>>)> {
+    match expr_inner(ast) {
+        Token::FunctionCall(name, args) => Some((name, args)),
+        _ => None
+    }
+}
+
+// Whatever's under the layers that only have one thing in them
+fn expr_inner(ast: &Token) -> &Token {
+    match ast {
+        Token::Expression(inner, None, None)
+            | Token::UnaryExpression(inner, None)
+            | Token::ExponentialExpression(inner, None)
+            | Token::Term(inner) => expr_inner(inner),
+        Token::OrExpression(inner, rest)
+            | Token::XorExpression(inner, rest)
+            | Token::AndExpression(inner, rest)
+            | Token::RelationalExpression(inner, rest)
+            | Token::ShiftExpression(inner, rest)
+            | Token::SumExpression(inner, rest)
+            | Token::ProductExpression(inner, rest) if rest.is_empty() => expr_inner(inner),
+        _ => ast
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        eval, Environment
+    };
+    use crate::parser::parse_stmt;
+
+    fn show(expr: &str) -> String {
+        let mut env = Environment::new();
+        match eval(&parse_stmt(expr).unwrap(), &mut env) {
+            Ok(res) => res.to_string(&env.settings),
+            Err(err) => format!("Error: {}", err)
+        }
+    }
+
+    // hex(x) and friends only change how the result is shown, see eval_in_base
+    #[test]
+    fn bases() {
+        assert_eq!(show("hex(0xff_ff_ + 1_)"), "0x10000");
+        assert_eq!(show("bin([0_ - 5_, 2_])"), "[ -0b101 0b10 ]");
+        assert_eq!(show("base(35_, 36_)"), "36#Z");
+        assert_eq!(show("hex(1.5)"), "Error: Type mismatch: 'hex' only works on ints and fractions");
+        assert_eq!(show("base(1_, 37_)"), "Error: Domain error: There's no base 37 to show numbers in");
+    }
+}
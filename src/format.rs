@@ -0,0 +1,348 @@
+/*
+ * Author: Dylan Turner
+ * Description:
+ * - Turn numbers into text following the display settings (digits, notation, grouping, complex style)
+ * - Every kind of number is broken down into a sign, its significant digits and where the point goes,
+ *   so they all get written out the same way
+ */
+
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::Signed;
+use crate::{
+    int::Int,
+    decimal::Decimal,
+    complex::{
+        FComplex, IComplex
+    }, settings::{
+        Settings, Notation, ComplexStyle, Digits
+    }
+};
+
+// Binary floats only have about 15-17 digits, and the last couple are mostly noise like 0.1 + 0.2's
+// 0.30000000000000004, so by default they're rounded to 15. ':set digits all' shows them all
+pub const FLOAT_DIGITS: u32 = 15;
+
+// Ints are exact, so they only lose digits when shown in scientific or engineering notation
+pub fn format_int(int: &Int, settings: &Settings) -> String {
+    let big = int.to_big();
+    match (settings.base, settings.word) {
+        (10, _) => {},
+        // Fixed width ints show every bit they have, so negative ones are in two's complement
+        (base, Some(word)) => return render_base(&word.bits_of(int), base, word.width(base), settings.grouping),
+        (base, None) => return render_base(&big, base, 0, settings.grouping)
+    }
+    match (settings.notation, settings.digits) {
+        (Notation::Auto | Notation::Fixed, _) | (_, Digits::Auto | Digits::All) => {
+            let (neg, digits, point) = int_parts(&big);
+            render(neg, &digits, point, settings.notation, settings.grouping)
+        }, (notation, Digits::Count(digits)) => {
+            let (neg, digits, point) = decimal_parts(&Decimal::from_int(&big, digits));
+            render(neg, &digits, point, notation, settings.grouping)
+        }
+    }
+}
+
+// Fractions are always written as exact num/den
+pub fn format_ratio(ratio: &BigRational, settings: &Settings) -> String {
+    let plain = |big: &BigInt| if settings.base != 10 {
+        render_base(big, settings.base, 0, settings.grouping)
+    } else {
+        let (neg, digits, point) = int_parts(big);
+        render(neg, &digits, point, Notation::Fixed, settings.grouping)
+    };
+    format!("{}/{}", plain(ratio.numer()), plain(ratio.denom()))
+}
+
+pub fn format_float(num: f64, settings: &Settings) -> String {
+    if !num.is_finite() {
+        return num.to_string();
+    }
+
+    // Rust's {:e} gives the shortest digits that read back as the same f64, or rounds to a set amount
+    let text = match settings.digits {
+        Digits::All => format!("{:e}", num.abs()),
+        Digits::Auto => format!("{:.*e}", FLOAT_DIGITS as usize - 1, num.abs()),
+        Digits::Count(digits) => format!("{:.*e}", digits as usize - 1, num.abs())
+    };
+    let (mant, exp) = text.split_once('e').unwrap();
+    let digits = mant.replace('.', "").trim_end_matches('0').to_string();
+    let point = exp.parse::<i64>().unwrap() + 1;
+
+    let notation = match settings.notation {
+        Notation::Auto => Notation::Fixed,
+        notation => notation
+    };
+    render(num < 0.0, &digits, point, notation, settings.grouping)
+}
+
+pub fn format_decimal(num: &Decimal, settings: &Settings) -> String {
+    let num = match settings.digits {
+        Digits::Count(digits) if digits < num.prec => num.with_prec(digits),
+        _ => num.clone()
+    };
+    let (neg, digits, point) = decimal_parts(&num);
+
+    // Same switch over to scientific as when they're printed normally
+    let notation = match settings.notation {
+        Notation::Auto if point > (num.prec as i64).max(21) || point < -6 => Notation::Scientific,
+        Notation::Auto => Notation::Fixed,
+        notation => notation
+    };
+    render(neg, &digits, point, notation, settings.grouping)
+}
+
+pub fn format_icomplex(num: &IComplex, settings: &Settings) -> String {
+    if num.is_real() {
+        return format_int(&num.re, settings);
+    }
+    match settings.complex_style {
+        ComplexStyle::Rect => rect(
+            (!num.re.is_zero()).then(|| format_int(&num.re, settings)),
+            format_int(&Int::from_big(num.im.to_big().abs()), settings),
+            num.im.is_negative()
+        ), _ => polar(num.to_fcomplex(), settings)
+    }
+}
+
+pub fn format_fcomplex(num: FComplex, settings: &Settings) -> String {
+    if num.is_real() {
+        return format_float(num.re, settings);
+    }
+    match settings.complex_style {
+        ComplexStyle::Rect => rect(
+            (num.re != 0.0).then(|| format_float(num.re, settings)),
+            format_float(num.im.abs(), settings),
+            num.im < 0.0
+        ), _ => polar(num, settings)
+    }
+}
+
+// a + jb, the same way they're typed in
+fn rect(re: Option<String>, im_abs: String, im_neg: bool) -> String {
+    match (re, im_neg) {
+        (None, false) => format!("j{}", im_abs),
+        (None, true) => format!("-j{}", im_abs),
+        (Some(re), false) => format!("{} + j{}", re, im_abs),
+        (Some(re), true) => format!("{} - j{}", re, im_abs)
+    }
+}
+
+fn polar(num: FComplex, settings: &Settings) -> String {
+    match settings.complex_style {
+        ComplexStyle::PolarDeg => format!(
+            "{}∠{}°", format_float(num.len(), settings), format_float(num.angle().to_degrees(), settings)
+        ), _ => format!("{}∠{}", format_float(num.len(), settings), format_float(num.angle(), settings))
+    }
+}
+
+// (negative, digits without trailing zeros, point) where |x| = 0.digits * 10^point
+fn int_parts(big: &BigInt) -> (bool, String, i64) {
+    let digits = big.abs().to_string();
+    let point = digits.len() as i64;
+    (big.is_negative(), String::from(digits.trim_end_matches('0')), point)
+}
+
+fn decimal_parts(num: &Decimal) -> (bool, String, i64) {
+    let digits = num.mant.abs().to_string();
+    let point = digits.len() as i64 + num.exp;
+    (num.is_negative(), String::from(digits.trim_end_matches('0')), point)
+}
+
+// Lay out the parts of a number. Auto should already be settled into one of the others
+fn render(neg: bool, digits: &str, point: i64, notation: Notation, grouping: bool) -> String {
+    if digits.is_empty() {
+        return String::from("0");
+    }
+    let sign = if neg { "-" } else { "" };
+
+    match notation {
+        Notation::Scientific | Notation::Engineering => {
+            // Engineering moves extra digits in front of the point to get the exponent to a multiple of 3
+            let exp = point - 1;
+            let shift = if notation == Notation::Engineering { exp.rem_euclid(3) } else { 0 };
+            let whole_len = shift as usize + 1;
+            let padded = format!("{:0<width$}", digits, width = whole_len);
+            let (whole, frac) = padded.split_at(whole_len);
+            let frac = if frac.is_empty() { String::new() } else { format!(".{}", frac) };
+            format!("{}{}{}e{}", sign, whole, frac, exp - shift)
+        }, _ => {
+            let (whole, frac) = if point <= 0 {
+                (String::from("0"), format!("{}{}", "0".repeat((-point) as usize), digits))
+            } else if point as usize >= digits.len() {
+                (format!("{}{}", digits, "0".repeat(point as usize - digits.len())), String::new())
+            } else {
+                let (whole, frac) = digits.split_at(point as usize);
+                (String::from(whole), String::from(frac))
+            };
+            let whole = if grouping { group(&whole, 3, ',') } else { whole };
+            let frac = if frac.is_empty() { String::new() } else { format!(".{}", frac) };
+            format!("{}{}{}", sign, whole, frac)
+        }
+    }
+}
+
+// Other bases are always exact, with a prefix saying which base it is, e.g. 0xFF or 36#Z
+// Grouping uses '_' like int literals do, in nibbles for hex and binary
+// Leading zeros are added to make it at least width digits long
+fn render_base(big: &BigInt, base: u32, width: usize, grouping: bool) -> String {
+    let digits = format!("{:0>width$}", big.abs().to_str_radix(base).to_uppercase(), width = width);
+    let digits = match (grouping, base) {
+        (false, _) => digits,
+        (true, 2 | 16) => group(&digits, 4, '_'),
+        (true, _) => group(&digits, 3, '_')
+    };
+    let prefix = match base {
+        16 => String::from("0x"),
+        8 => String::from("0o"),
+        2 => String::from("0b"),
+        _ => format!("{}#", base)
+    };
+    format!("{}{}{}", if big.is_negative() { "-" } else { "" }, prefix, digits)
+}
+
+// e.g. 1234567 -> 1,234,567
+fn group(digits: &str, size: usize, sep: char) -> String {
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(size) {
+            out.push(sep);
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::BigRational;
+    use super::{
+        format_int, format_ratio, format_float, format_decimal, format_icomplex, format_fcomplex, render
+    };
+    use crate::{
+        int::Int,
+        word::Word,
+        decimal::Decimal,
+        complex::{
+            FComplex, IComplex
+        }, settings::{
+            Settings, Digits, Notation, ComplexStyle
+        }
+    };
+
+    fn in_base(base: u32, grouping: bool) -> Settings {
+        Settings { base, grouping, ..Settings::new() }
+    }
+
+    fn with(notation: Notation, digits: Digits, grouping: bool) -> Settings {
+        Settings { notation, digits, grouping, ..Settings::new() }
+    }
+
+    // 0.digits * 10^point, laid out each way
+    #[test]
+    fn layouts() {
+        let fixed = |neg: bool, digits: &str, point: i64| render(neg, digits, point, Notation::Fixed, false);
+        assert_eq!(fixed(false, "", 5), "0");
+        assert_eq!(fixed(false, "125", 1), "1.25");
+        assert_eq!(fixed(true, "125", 5), "-12500");
+        assert_eq!(fixed(false, "125", 0), "0.125");
+        assert_eq!(fixed(false, "125", -2), "0.00125");
+        assert_eq!(render(false, "1234567", 7, Notation::Fixed, true), "1,234,567");
+        assert_eq!(render(true, "12345675", 4, Notation::Fixed, true), "-1,234.5675");
+        assert_eq!(render(false, "123", 2, Notation::Fixed, true), "12.3");
+
+        let sci = |digits: &str, point: i64| render(false, digits, point, Notation::Scientific, false);
+        assert_eq!(sci("125", 3), "1.25e2");
+        assert_eq!(sci("1", 1), "1e0");
+        assert_eq!(sci("125", -2), "1.25e-3");
+        assert_eq!(render(true, "5", 1, Notation::Scientific, true), "-5e0");
+
+        // Engineering pads with zeros when there aren't enough digits to fill in before the point
+        let eng = |digits: &str, point: i64| render(false, digits, point, Notation::Engineering, false);
+        assert_eq!(eng("125", 3), "125e0");
+        assert_eq!(eng("125", 4), "1.25e3");
+        assert_eq!(eng("125", 5), "12.5e3");
+        assert_eq!(eng("1", 3), "100e0");
+        assert_eq!(eng("1", 2), "10e0");
+        assert_eq!(eng("12", 0), "120e-3");
+        assert_eq!(eng("12", -1), "12e-3");
+        assert_eq!(eng("12", -2), "1.2e-3");
+    }
+
+    #[test]
+    fn notations() {
+        let big = Int::from(123_456_789);
+        assert_eq!(format_int(&big, &with(Notation::Auto, Digits::Count(3), false)), "123456789");
+        assert_eq!(format_int(&big, &with(Notation::Fixed, Digits::Count(3), true)), "123,456,789");
+        assert_eq!(format_int(&big, &with(Notation::Scientific, Digits::Auto, false)), "1.23456789e8");
+        assert_eq!(format_int(&big, &with(Notation::Scientific, Digits::Count(3), false)), "1.23e8");
+        assert_eq!(format_int(&big, &with(Notation::Engineering, Digits::Count(4), false)), "123.5e6");
+        assert_eq!(format_int(&Int::from(-1000), &with(Notation::Engineering, Digits::All, false)), "-1e3");
+
+        assert_eq!(format_float(0.00012, &with(Notation::Auto, Digits::Auto, false)), "0.00012");
+        assert_eq!(format_float(0.00012, &with(Notation::Engineering, Digits::Auto, false)), "120e-6");
+        assert_eq!(format_float(1234.5, &with(Notation::Fixed, Digits::Auto, true)), "1,234.5");
+        assert_eq!(format_float(f64::INFINITY, &Settings::new()), "inf");
+
+        // Decimals only switch to scientific on their own when they'd be too long to write out
+        let dec = |text: &str| Decimal::parse(text, 30).unwrap();
+        let auto = with(Notation::Auto, Digits::Auto, false);
+        assert_eq!(format_decimal(&dec("1.5e20"), &auto), "150000000000000000000");
+        assert_eq!(format_decimal(&dec("1.5e40"), &auto), "1.5e40");
+        assert_eq!(format_decimal(&dec("1.5e-7"), &auto), "0.00000015");
+        assert_eq!(format_decimal(&dec("1.5e-8"), &auto), "1.5e-8");
+        assert_eq!(format_decimal(&dec("2.345"), &with(Notation::Auto, Digits::Count(2), false)), "2.3");
+        assert_eq!(format_decimal(&dec("12345"), &with(Notation::Engineering, Digits::Auto, false)), "12.345e3");
+    }
+
+    #[test]
+    fn complex_styles() {
+        let style = |complex_style: ComplexStyle| Settings { complex_style, ..Settings::new() };
+        let (rect, polar, deg) = (style(ComplexStyle::Rect), style(ComplexStyle::Polar), style(ComplexStyle::PolarDeg));
+        let gauss = |re: i64, im: i64| IComplex::new_cardinal(Int::from(re), Int::from(im));
+        assert_eq!(format_icomplex(&gauss(3, 4), &rect), "3 + j4");
+        assert_eq!(format_icomplex(&gauss(3, -4), &rect), "3 - j4");
+        assert_eq!(format_icomplex(&gauss(0, -4), &rect), "-j4");
+        assert_eq!(format_icomplex(&gauss(-3, 0), &polar), "-3");
+        assert_eq!(format_icomplex(&gauss(0, 2), &polar), "2∠1.5707963267949");
+        assert_eq!(format_icomplex(&gauss(1, -1), &deg), "1.4142135623731∠-45°");
+
+        assert_eq!(format_fcomplex(FComplex::new_cardinal(0.0, 0.5), &rect), "j0.5");
+        assert_eq!(format_fcomplex(FComplex::new_cardinal(-1.5, -0.5), &rect), "-1.5 - j0.5");
+        assert_eq!(format_fcomplex(FComplex::new_cardinal(-2.0, 0.0), &deg), "-2");
+        assert_eq!(format_fcomplex(FComplex::new_cardinal(0.0, -2.0), &deg), "2∠-90°");
+        assert_eq!(format_fcomplex(FComplex::new_cardinal(-1.0, 1.0), &polar), "1.4142135623731∠2.35619449019234");
+    }
+
+    #[test]
+    fn float_digits() {
+        let show = |num: f64, digits: Digits| format_float(num, &Settings { digits, ..Settings::new() });
+        assert_eq!(show(0.1 + 0.2, Digits::Auto), "0.3");
+        assert_eq!(show(2.0 / 3.0, Digits::Auto), "0.666666666666667");
+        assert_eq!(show(-1e-20 / 3.0, Digits::Auto), "-0.00000000000000000000333333333333333");
+        assert_eq!(show(0.1 + 0.2, Digits::All), "0.30000000000000004");
+        assert_eq!(show(2.0 / 3.0, Digits::Count(3)), "0.667");
+        assert_eq!(show(1.5, Digits::Auto), "1.5");
+    }
+
+    #[test]
+    fn bases() {
+        assert_eq!(format_int(&Int::from(65536), &in_base(16, true)), "0x1_0000");
+        assert_eq!(format_int(&Int::from(65536), &in_base(16, false)), "0x10000");
+        assert_eq!(format_int(&Int::from(-5), &in_base(2, true)), "-0b101");
+        assert_eq!(format_int(&Int::from(35), &in_base(36, false)), "36#Z");
+        assert_eq!(format_int(&Int::from(1_000_000), &in_base(8, true)), "0o3_641_100");
+        let three_eighths = BigRational::new(3.into(), 8.into());
+        assert_eq!(format_ratio(&three_eighths, &in_base(8, false)), "0o3/0o10");
+    }
+
+    // Words show every bit they have, so negatives come out in two's complement
+    #[test]
+    fn word_bases() {
+        let word = |name: &str, base: u32| Settings { word: Word::parse(name), ..in_base(base, false) };
+        assert_eq!(format_int(&Int::from(-2), &word("i16", 16)), "0xFFFE");
+        assert_eq!(format_int(&Int::from(5), &word("u8", 2)), "0b00000101");
+        assert_eq!(format_int(&Int::from(-1), &word("i12", 8)), "0o7777");
+        assert_eq!(format_int(&Int::from(-1), &word("i8", 10)), "-1");
+    }
+}
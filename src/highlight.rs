@@ -0,0 +1,149 @@
+/*
+ * Author: Dylan Turner
+ * Description:
+ * - Colors for the repl input line, using the parser's own tokenizers so it matches what runs
+ * - Also points out the bracket matching the one at the cursor, and brackets with no partner
+ * - Earlier lines of a statement that's continued are taken into account for brackets and names
+ */
+
+use termion::{
+    color::{
+        Fg, Bg, Reset, Blue, Cyan, Green, Yellow, Magenta, Red, LightBlack
+    }, style
+};
+use crate::{
+    eval::Environment,
+    builtin::BUILTIN_HELP,
+    parser::{
+        Token, parse_integer, parse_number, parse_ident
+    }
+};
+
+// Give back the line with color codes in it. Doesn't change what's visible, so the cursor
+// still lines up. 'before' is whatever came before the line in the same statement, newlines included
+pub fn highlight(before: &str, line: &str, cursor: usize, env: &Environment) -> String {
+    let full = format!("{}{}", before, line);
+    let chars = full.char_indices().collect::<Vec<(usize, char)>>();
+    let (partner, unmatched) = match_brackets(&chars, before.chars().count() + cursor);
+    let locals = local_names(&full);
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < line.len() {
+        let rest = line.split_at(i).1;
+
+        // Each case gives back the color and how many bytes it covers
+        let (color, len) = if rest.starts_with('#') {
+            (format!("{}", Fg(LightBlack)), rest.len())
+        } else if let Some(int) = parse_integer(rest) {
+            (format!("{}", Fg(Cyan)), token_len(&int.token))
+        } else if let Some(num) = parse_number(rest) {
+            (format!("{}", Fg(Blue)), token_len(&num.token))
+        } else if let Some(ident) = parse_ident(rest) {
+            let name = match &ident.token {
+                Token::Identifier(name) => name.as_str(),
+                _ => unreachable!("parse_ident only gives identifiers")
+            };
+            let is_call = rest.split_at(name.len()).1.trim_start().starts_with('(');
+            let color = if name == "let" || name == "xor" {
+                format!("{}", Fg(Magenta))
+            } else if is_call && BUILTIN_HELP.iter().any(|(builtin, _, _)| *builtin == name) {
+                format!("{}", Fg(Yellow))
+            } else if (is_call && env.funcs.contains_key(name))
+                    || (!is_call && env.get_var(name).is_some())
+                    || locals.iter().any(|local| local == name) {
+                format!("{}", Fg(Green))
+            } else {
+                // Would fail with an unknown name if it were run now
+                format!("{}", Fg(Red))
+            };
+            (color, name.len())
+        } else {
+            let c = rest.chars().next().unwrap();
+            let color = if unmatched.contains(&(before.len() + i)) {
+                format!("{}{}", Bg(Red), style::Bold)
+            } else if partner.contains(&(before.len() + i)) {
+                format!("{}{}", Bg(LightBlack), style::Bold)
+            } else if "+-*/^=<>?:,\\j&|~".contains(c) {
+                format!("{}", Fg(Magenta))
+            } else {
+                String::new()
+            };
+            (color, c.len_utf8())
+        };
+
+        let text = rest.split_at(len).0;
+        if color.is_empty() {
+            out.push_str(text);
+        } else {
+            out.push_str(format!("{}{}{}{}", color, text, Fg(Reset), style::Reset).as_str());
+        }
+        i += len;
+    }
+    out
+}
+
+fn token_len(token: &Token) -> usize {
+    match token {
+        Token::Integer(text) | Token::Number(text) | Token::Identifier(text) => text.len(),
+        _ => unreachable!("Only called on literals and identifiers")
+    }
+}
+
+// Byte positions of the bracket paired with the one at (or just before) the cursor,
+// and of every bracket that has no partner
+fn match_brackets(chars: &[(usize, char)], cursor: usize) -> (Vec<usize>, Vec<usize>) {
+    let mut stack: Vec<(usize, usize)> = Vec::new(); // (char index, byte position)
+    let mut pairs = Vec::new();
+    let mut unmatched = Vec::new();
+    let mut in_comment = false;
+    for (idx, (pos, c)) in chars.iter().enumerate() {
+        match c {
+            '#' => in_comment = true,
+            '\n' => in_comment = false,
+            _ if in_comment => {},
+            '(' | '[' => stack.push((idx, *pos)),
+            ')' | ']' => {
+                let open = if *c == ')' { '(' } else { '[' };
+                match stack.pop() {
+                    Some((open_idx, open_pos)) if chars[open_idx].1 == open => {
+                        pairs.push((open_idx, idx, open_pos, *pos));
+                    }, Some((_, open_pos)) => {
+                        unmatched.push(open_pos);
+                        unmatched.push(*pos);
+                    }, None => unmatched.push(*pos)
+                }
+            }, _ => {}
+        }
+    }
+    unmatched.extend(stack.iter().map(|(_, pos)| *pos));
+
+    // The bracket just before the cursor wins, since that's usually the one just typed
+    let mut partner = Vec::new();
+    for at in [ cursor.wrapping_sub(1), cursor ] {
+        if let Some((_, _, open_pos, close_pos)) = pairs.iter()
+                .find(|(open_idx, close_idx, _, _)| *open_idx == at || *close_idx == at) {
+            partner.push(*open_pos);
+            partner.push(*close_pos);
+            break;
+        }
+    }
+    (partner, unmatched)
+}
+
+// Names being defined by the line itself, i.e. a function's name and args, or a 'let' target
+fn local_names(line: &str) -> Vec<String> {
+    let line = line.trim_start();
+    if let Some(def) = line.strip_prefix('\\') {
+        let name = def.split('(').next().unwrap_or("").trim();
+        let args = def.split('(').nth(1).and_then(|args| args.split(')').next()).unwrap_or("");
+        let mut names = vec![ String::from(name) ];
+        names.extend(args.split(',').map(|arg| String::from(arg.trim())));
+        names
+    } else if let Some(asgn) = line.strip_prefix("let ") {
+        vec![ asgn.split(|c: char| !c.is_alphanumeric() && c != '_').find(|name| !name.is_empty())
+            .map(String::from).unwrap_or_default() ]
+    } else {
+        Vec::new()
+    }
+}
@@ -0,0 +1,261 @@
+/*
+ * Author: Dylan Turner
+ * Description:
+ * - Integer type that's an i64 until it can't be
+ * - Every op is checked, and anything that would overflow gets redone as a BigInt
+ */
+
+use std::{
+    cmp::Ordering,
+    fmt::{
+        Display, Formatter, Result as FmtResult
+    }, ops::{
+        Add, Sub, Mul, Neg, BitAnd, BitOr, BitXor, Not
+    }, str::FromStr
+};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{
+    Signed, ToPrimitive
+};
+
+// Results that would be bigger than this are refused instead of taking forever to build and print
+pub const MAX_RESULT_BITS: u64 = 1 << 20;
+
+#[derive(Clone, Debug)]
+pub enum Int {
+    Small(i64),
+    Big(BigInt) // Only ever holds values outside of i64's range
+}
+
+impl Int {
+    pub fn from_big(big: BigInt) -> Self {
+        match big.to_i64() {
+            Some(small) => Int::Small(small),
+            None => Int::Big(big)
+        }
+    }
+
+    pub fn to_big(&self) -> BigInt {
+        match self {
+            Int::Small(small) => BigInt::from(*small),
+            Int::Big(big) => big.clone()
+        }
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        match self {
+            Int::Small(small) => Some(*small),
+            Int::Big(_) => None
+        }
+    }
+
+    // Very big values become +/-inf
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Int::Small(small) => *small as f64,
+            Int::Big(big) => big.to_f64().unwrap_or(f64::NAN)
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        matches!(self, Int::Small(0))
+    }
+
+    pub fn is_negative(&self) -> bool {
+        match self {
+            Int::Small(small) => *small < 0,
+            Int::Big(big) => big.is_negative()
+        }
+    }
+
+    // Bits needed for |x|, and 0 for 0
+    pub fn bits(&self) -> u64 {
+        match self {
+            Int::Small(small) => 64 - small.unsigned_abs().leading_zeros() as u64,
+            Int::Big(big) => big.bits()
+        }
+    }
+
+    pub fn div_floor(&self, other: &Self) -> Self {
+        match (self, other) {
+            // i64::MIN / -1 is the one small division that overflows
+            (Int::Small(a), Int::Small(b)) if !(*a == i64::MIN && *b == -1) =>
+                Int::Small(a.div_floor(b)),
+            _ => Int::from_big(self.to_big().div_floor(&other.to_big()))
+        }
+    }
+
+    pub fn pow(&self, n: u64) -> Self {
+        if let Int::Small(small) = self {
+            if let Some(res) = u32::try_from(n).ok().and_then(|n| small.checked_pow(n)) {
+                return Int::Small(res);
+            }
+        }
+
+        let mut base = self.to_big();
+        let mut result = BigInt::from(1);
+        let mut n = n;
+        while n > 0 {
+            if n & 1 == 1 {
+                result *= &base;
+            }
+            base = &base * &base;
+            n >>= 1;
+        }
+        Int::from_big(result)
+    }
+
+    // Bits shifted past the top carry on into a BigInt instead of being lost
+    pub fn shl(&self, n: u32) -> Self {
+        if let Int::Small(small) = self {
+            if let Some(res) = small.checked_shl(n).filter(|res| res >> n == *small) {
+                return Int::Small(res);
+            }
+        }
+        Int::from_big(self.to_big() << n)
+    }
+
+    // Rounds down like an arithmetic shift, so negative numbers end up at -1 rather than 0
+    pub fn shr(&self, n: u32) -> Self {
+        match self {
+            Int::Small(small) => Int::Small(small >> n.min(63)),
+            Int::Big(big) => Int::from_big(big >> n)
+        }
+    }
+
+    // Do a checked i64 op and only fall back to BigInt when it overflows
+    fn arith(
+            self, other: Self,
+            small_op: fn(i64, i64) -> Option<i64>, big_op: fn(BigInt, BigInt) -> BigInt) -> Self {
+        if let (Int::Small(a), Int::Small(b)) = (&self, &other) {
+            if let Some(res) = small_op(*a, *b) {
+                return Int::Small(res);
+            }
+        }
+        Int::from_big(big_op(self.to_big(), other.to_big()))
+    }
+}
+
+impl From<i64> for Int {
+    fn from(small: i64) -> Self {
+        Int::Small(small)
+    }
+}
+
+// Also takes a 0x, 0o or 0b prefix for hex, octal and binary
+impl FromStr for Int {
+    type Err = String;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (radix, digits) = match text.get(..2) {
+            Some("0x") => (16, text.split_at(2).1),
+            Some("0o") => (8, text.split_at(2).1),
+            Some("0b") => (2, text.split_at(2).1),
+            _ => (10, text)
+        };
+        match i64::from_str_radix(digits, radix) {
+            Ok(small) => Ok(Int::Small(small)),
+            Err(_) => BigInt::parse_bytes(digits.as_bytes(), radix)
+                .map(Int::from_big)
+                .ok_or_else(|| format!("'{}' is not an integer", text))
+        }
+    }
+}
+
+impl Display for Int {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Int::Small(small) => write!(f, "{}", small),
+            Int::Big(big) => write!(f, "{}", big)
+        }
+    }
+}
+
+impl Add for Int {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        self.arith(other, i64::checked_add, |a, b| a + b)
+    }
+}
+
+impl Sub for Int {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        self.arith(other, i64::checked_sub, |a, b| a - b)
+    }
+}
+
+impl Mul for Int {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        self.arith(other, i64::checked_mul, |a, b| a * b)
+    }
+}
+
+// Negative numbers act like two's complement with as many leading 1s as they need
+impl BitAnd for Int {
+    type Output = Self;
+    fn bitand(self, other: Self) -> Self {
+        self.arith(other, |a, b| Some(a & b), |a, b| a & b)
+    }
+}
+
+impl BitOr for Int {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self {
+        self.arith(other, |a, b| Some(a | b), |a, b| a | b)
+    }
+}
+
+impl BitXor for Int {
+    type Output = Self;
+    fn bitxor(self, other: Self) -> Self {
+        self.arith(other, |a, b| Some(a ^ b), |a, b| a ^ b)
+    }
+}
+
+// ~x = -x - 1
+impl Not for Int {
+    type Output = Self;
+    fn not(self) -> Self {
+        match self {
+            Int::Small(small) => Int::Small(!small),
+            Int::Big(big) => Int::from_big(!big)
+        }
+    }
+}
+
+impl Neg for Int {
+    type Output = Self;
+    fn neg(self) -> Self {
+        match self {
+            Int::Small(small) => match small.checked_neg() {
+                Some(res) => Int::Small(res),
+                None => Int::from_big(-BigInt::from(small))
+            }, Int::Big(big) => Int::from_big(-big)
+        }
+    }
+}
+
+impl PartialEq for Int {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Int {}
+
+impl PartialOrd for Int {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Int {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Int::Small(a), Int::Small(b)) => a.cmp(b),
+            _ => self.to_big().cmp(&other.to_big())
+        }
+    }
+}
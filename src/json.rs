@@ -0,0 +1,238 @@
+/*
+ * Author: Dylan Turner
+ * Description:
+ * - JSON versions of results and errors for --json, so other programs don't have to scrape text
+ * - Every value says what type it is, and numbers are written out exactly instead of rounded
+ */
+
+use crate::{
+    var::Var,
+    eval::Outcome,
+    error::{
+        ParseError, CalcError
+    }
+};
+
+// One object per statement, e.g. {"kind":"expr","value":{"type":"int","value":4}}
+pub fn outcome_json(outcome: &Outcome) -> String {
+    match outcome {
+        Outcome::Value(val) => format!("{{\"kind\":\"expr\",\"value\":{}}}", var_json(val)),
+        Outcome::InBase(val, base) => format!(
+            "{{\"kind\":\"expr\",\"value\":{},\"base\":{}}}", var_json(val), base
+        ), Outcome::VarAssigned { name, value, updated } => format!(
+            "{{\"kind\":\"assignment\",\"name\":{},\"value\":{},\"updated\":{}}}",
+            string(name), var_json(value), updated
+        ), Outcome::FunctionDefined { name, args, updated } => format!(
+            "{{\"kind\":\"function-def\",\"name\":{},\"args\":[{}],\"updated\":{}}}",
+            string(name), args.iter().map(|arg| string(arg)).collect::<Vec<String>>().join(","), updated
+        ), Outcome::Flagged(inner, flags) => {
+            // The same object as without them, with the flags added on the end
+            let inner = outcome_json(inner);
+            format!(
+                "{},\"flags\":{{\"carry\":{},\"overflow\":{}}}}}",
+                inner.strip_suffix('}').unwrap_or(&inner), flags.carry, flags.overflow
+            )
+        }
+    }
+}
+
+pub fn command_json(output: &str) -> String {
+    format!("{{\"kind\":\"command\",\"output\":{}}}", string(output))
+}
+
+// Line and column are where the error is in the input, counting from 1
+pub fn parse_error_json(err: &ParseError, line: usize, col: usize) -> String {
+    let expected = err.expected.iter().map(|exp| string(exp)).collect::<Vec<String>>().join(",");
+    format!(
+        "{{\"kind\":\"error\",\"error\":{{\"type\":\"parse\",\"message\":{},\"expected\":[{}],\"line\":{},\"col\":{}}}}}",
+        string(&err.message()), expected, line, col
+    )
+}
+
+pub fn calc_error_json(err: &CalcError, line: usize, col: usize) -> String {
+    let kind = match err {
+        CalcError::UnknownVariable(_) => "unknown-variable",
+        CalcError::UnknownFunction(_) => "unknown-function",
+        CalcError::Arity { .. } => "arity",
+        CalcError::TypeMismatch(_) => "type-mismatch",
+        CalcError::Domain(_) => "domain",
+        CalcError::Plugin(_) => "plugin",
+        CalcError::DivisionByZero => "division-by-zero",
+        CalcError::InvalidCommand(_) => "invalid-command"
+    };
+    format!(
+        "{{\"kind\":\"error\",\"error\":{{\"type\":{},\"message\":{},\"line\":{},\"col\":{}}}}}",
+        string(kind), string(&err.to_string()), line, col
+    )
+}
+
+// Complex numbers are split into typed real and imaginary parts, so they're never just a string
+pub fn var_json(var: &Var) -> String {
+    match var {
+        Var::Int(int) if int.is_real() => scalar("int", &int.re.to_string()),
+        Var::Int(int) => complex(scalar("int", &int.re.to_string()), scalar("int", &int.im.to_string())),
+        Var::Rational(ratio) => format!(
+            "{{\"type\":\"rational\",\"num\":{},\"den\":{}}}", ratio.numer(), ratio.denom()
+        ), Var::Float(num) if num.is_real() => float(num.re),
+        Var::Float(num) => complex(float(num.re), float(num.im)),
+        Var::Decimal(num) => scalar("decimal", &num.to_string()),
+        Var::List(items) => format!(
+            "{{\"type\":\"list\",\"items\":[{}]}}",
+            items.iter().map(var_json).collect::<Vec<String>>().join(",")
+        )
+    }
+}
+
+fn scalar(kind: &str, value: &str) -> String {
+    format!("{{\"type\":\"{}\",\"value\":{}}}", kind, value)
+}
+
+fn complex(re: String, im: String) -> String {
+    format!("{{\"type\":\"complex\",\"re\":{},\"im\":{}}}", re, im)
+}
+
+// JSON has no NaN or infinity, so those become strings
+fn float(num: f64) -> String {
+    if num.is_finite() {
+        scalar("float", &format!("{:?}", num))
+    } else {
+        scalar("float", &string(&num.to_string()))
+    }
+}
+
+fn string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(format!("\\u{:04x}", c as u32).as_str()),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use num_rational::BigRational;
+    use super::{
+        outcome_json, command_json, parse_error_json, calc_error_json, var_json
+    };
+    use crate::{
+        int::Int,
+        var::Var,
+        eval::Outcome,
+        word::Flags,
+        decimal::Decimal,
+        complex::{
+            FComplex, IComplex
+        }, error::{
+            ParseError, ParseErrorKind, CalcError
+        }
+    };
+
+    fn int(re: i64, im: i64) -> Var {
+        Var::Int(IComplex::new_cardinal(Int::from(re), Int::from(im)))
+    }
+
+    fn float(re: f64, im: f64) -> Var {
+        Var::Float(FComplex::new_cardinal(re, im))
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(var_json(&int(-4, 0)), "{\"type\":\"int\",\"value\":-4}");
+        assert_eq!(var_json(&float(0.5, 0.0)), "{\"type\":\"float\",\"value\":0.5}");
+        assert_eq!(var_json(&float(2.0, 0.0)), "{\"type\":\"float\",\"value\":2.0}");
+        assert_eq!(var_json(&float(f64::INFINITY, 0.0)), "{\"type\":\"float\",\"value\":\"inf\"}");
+        assert_eq!(
+            var_json(&Var::from_ratio(BigRational::new((-6).into(), 4.into()))),
+            "{\"type\":\"rational\",\"num\":-3,\"den\":2}"
+        );
+        assert_eq!(
+            var_json(&Var::Decimal(Decimal::parse("1.25", 30).unwrap())),
+            "{\"type\":\"decimal\",\"value\":1.25}"
+        );
+
+        // Each part of a complex number keeps its own type
+        assert_eq!(
+            var_json(&int(1, -2)),
+            "{\"type\":\"complex\",\"re\":{\"type\":\"int\",\"value\":1},\"im\":{\"type\":\"int\",\"value\":-2}}"
+        );
+        assert_eq!(
+            var_json(&float(1.5, 2.0)),
+            "{\"type\":\"complex\",\"re\":{\"type\":\"float\",\"value\":1.5},\"im\":{\"type\":\"float\",\"value\":2.0}}"
+        );
+
+        assert_eq!(var_json(&Var::List(vec![])), "{\"type\":\"list\",\"items\":[]}");
+        assert_eq!(
+            var_json(&Var::List(vec![int(1, 0), Var::List(vec![float(0.5, 0.0)])])),
+            "{\"type\":\"list\",\"items\":[{\"type\":\"int\",\"value\":1},\
+            {\"type\":\"list\",\"items\":[{\"type\":\"float\",\"value\":0.5}]}]}"
+        );
+    }
+
+    #[test]
+    fn outcomes() {
+        assert_eq!(
+            outcome_json(&Outcome::Value(int(4, 0))),
+            "{\"kind\":\"expr\",\"value\":{\"type\":\"int\",\"value\":4}}"
+        );
+        assert_eq!(
+            outcome_json(&Outcome::InBase(int(255, 0), 16)),
+            "{\"kind\":\"expr\",\"value\":{\"type\":\"int\",\"value\":255},\"base\":16}"
+        );
+        assert_eq!(
+            outcome_json(&Outcome::FunctionDefined {
+                name: String::from("f"), args: vec![String::from("x"), String::from("y")], updated: false
+            }), "{\"kind\":\"function-def\",\"name\":\"f\",\"args\":[\"x\",\"y\"],\"updated\":false}"
+        );
+
+        // Flags go on the end of the same object, whatever was flagged
+        let flags = Flags { carry: true, overflow: false };
+        assert_eq!(
+            outcome_json(&Outcome::Flagged(Box::new(Outcome::Value(int(0, 0))), flags)),
+            "{\"kind\":\"expr\",\"value\":{\"type\":\"int\",\"value\":0},\"flags\":{\"carry\":true,\"overflow\":false}}"
+        );
+        let assigned = Outcome::VarAssigned { name: String::from("a"), value: int(-128, 0), updated: true };
+        assert_eq!(
+            outcome_json(&Outcome::Flagged(Box::new(assigned), Flags { carry: false, overflow: true })),
+            "{\"kind\":\"assignment\",\"name\":\"a\",\"value\":{\"type\":\"int\",\"value\":-128},\"updated\":true,\
+            \"flags\":{\"carry\":false,\"overflow\":true}}"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let err = ParseError::new(ParseErrorKind::UnexpectedEnd, (4, 4), &["a number", "'('"]);
+        assert_eq!(
+            parse_error_json(&err, 2, 5),
+            "{\"kind\":\"error\",\"error\":{\"type\":\"parse\",\
+            \"message\":\"Expected a number or '(' but reached end of input\",\
+            \"expected\":[\"a number\",\"'('\"],\"line\":2,\"col\":5}}"
+        );
+        assert_eq!(
+            calc_error_json(&CalcError::UnknownVariable(String::from("x")), 1, 3),
+            "{\"kind\":\"error\",\"error\":{\"type\":\"unknown-variable\",\
+            \"message\":\"No such variable 'x'\",\"line\":1,\"col\":3}}"
+        );
+        assert_eq!(
+            calc_error_json(&CalcError::DivisionByZero, 3, 1),
+            "{\"kind\":\"error\",\"error\":{\"type\":\"division-by-zero\",\"message\":\"Division by zero\",\"line\":3,\"col\":1}}"
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(command_json("plain"), "{\"kind\":\"command\",\"output\":\"plain\"}");
+        assert_eq!(
+            command_json("say \"hi\"\\\n\tπ\r\u{1}"),
+            "{\"kind\":\"command\",\"output\":\"say \\\"hi\\\"\\\\\\n\\tπ\\r\\u0001\"}"
+        );
+    }
+}
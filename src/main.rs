@@ -3,33 +3,59 @@
  * Description: Entry point for calc
  */
 
+mod error;
 mod parser;
 mod var;
 mod eval;
 mod args;
 mod builtin;
 mod complex;
+mod int;
 mod plugin;
+mod decimal;
+mod settings;
+mod command;
+mod editor;
+mod complete;
+mod highlight;
+mod json;
+mod format;
+mod word;
 
 use std::{
-    path::Path,
-    fs::File,
+    cell::Cell,
+    path::{
+        Path, MAIN_SEPARATOR
+    },
+    fs::{
+        File, read_to_string
+    },
     process::exit,
+    iter::from_fn,
     io::{
-        stdout, stdin, Write, BufRead, BufReader
+        stdout, stdin, Write, BufRead, BufReader, Error as IoError, ErrorKind
     }
 };
 use dirs::config_dir;
 use termion::{
-    input::TermRead,
-    event::Key,
-    clear::CurrentLine
+    clear::CurrentLine,
+    is_tty
 };
 use crate::{
-    parser::parse_stmt,
-    args::cli_args,
+    parser::{
+        parse_stmt, is_incomplete, is_blank, strip_comment
+    }, args::cli_args,
+    editor::Editor,
+    complete::Continued,
+    command::{
+        is_command, is_quit, run_command
+    },
     eval::{
         eval, Environment
+    }, error::{
+        ParseError, CalcError
+    }, json::{
+        outcome_json, command_json, parse_error_json, calc_error_json
     }
 };
 
@@ -45,42 +71,57 @@ fn main() {
         if Path::new(&init_file).exists() {
             let file = File::open(init_file);
             if file.is_ok() {
-                for line in BufReader::new(file.unwrap()).lines() {
-                    let stmt = parse_stmt(&line.unwrap());
-                    match stmt {
-                        Err(err) => println!("Init File Error: {}", err),
-                        Ok(ast) => {
-                            // Only print errors
-                            let res = eval(&ast, &mut env);
-                            if res.starts_with("Error") {
-                                println!("Init File {}", res);
-                            }
-                        }
-                    }       
+                let read_failed = Cell::new(false);
+                let lines = read_lines(BufReader::new(file.unwrap()), "Init File", &read_failed);
+                for (_, stmt) in join_continued(lines) {
+                    // Only print errors, and on stderr so they stay out of the results (e.g. with --json)
+                    if let Err(err) = run_line(&stmt, &mut env) {
+                        eprintln!("Init File {}", err);
+                    }
+                }
+                if read_failed.get() {
+                    exit(1);
                 }
             }
         }
     }
 
     let args = cli_args();
-    if args.is_present("stmts") {
-        let lines = if args.value_of("stmts").unwrap() != "-" {
-            args.value_of("stmts").unwrap().split('\n')
-                .map(|s| String::from(s)).collect::<Vec<String>>()
-        } else {
-            let mut ls = Vec::new();
-            let inp = stdin();
-            for line in inp.lock().lines() {
-                ls.push(line.unwrap().clone());
-            }
-            ls
+    for setting in args.values_of("set").into_iter().flatten() {
+        let res = match setting.split_once('=') {
+            None => Err(CalcError::InvalidCommand(format!("Expected name=value, not '{}'", setting))),
+            Some((name, value)) => env.settings.set(name, value)
         };
-        for line in lines {
-            let stmt = parse_stmt(line.as_str());
-            match stmt {
-                Err(err) => println!("Error: {}", err),
-                Ok(ast) => println!("{}", eval(&ast, &mut env))
-            }
+        if let Err(err) = res {
+            eprintln!("Error: {}", err);
+            exit(1);
+        }
+    }
+
+    let stmts = args.value_of("stmts");
+    let script = args.value_of("file").or(stmts.filter(|stmts| is_script(stmts)));
+    let json = args.is_present("json");
+    if let Some(script) = script {
+        let text = read_to_string(script).unwrap_or_else(|err| {
+            eprintln!("{}: Error: Could not read file: {}", script, err);
+            exit(1);
+        });
+        let lines = text.lines().map(String::from);
+        if !run_stmts(lines, Some(script), args.is_present("keep-going"), json, &mut env) {
+            exit(1);
+        }
+    } else if stmts.is_some() || !is_tty(&stdin()) || !is_tty(&stdout()) {
+        // Either given statements directly, or being fed by a pipe/file/other program, which
+        // gets just the results, as each line comes in, without any prompts or line editing
+        let read_failed = Cell::new(false);
+        let lines: Box<dyn Iterator<Item = String>> = match stmts {
+            Some(stmts) if stmts != "-" => Box::new(
+                stmts.split('\n').map(String::from).collect::<Vec<String>>().into_iter()
+            ), _ => Box::new(read_lines(stdin().lock(), "stdin", &read_failed))
+        };
+        run_stmts(lines, None, true, json, &mut env);
+        if read_failed.get() {
+            exit(1);
         }
     } else {
         let mut out = stdout();
@@ -88,28 +129,186 @@ fn main() {
         writeln!(
             out, "{}CLI Calculator v1. Enter 'q' or press Ctrl-C to exit", CurrentLine
         ).expect("Could not write to stdout");
-        loop {
-            write!(out, "> ").expect("Could not write to stdout");
-            out.flush().unwrap();
-            let mut line = String::new();
-            let inp = stdin();
-
-            for c in inp.keys() {
-                match c.unwrap() {
-                    Key::Char('\n') => break,
-                    Key::Char('q') | Key::Ctrl('c') => exit(0),
-                    Key::Char(c) => {
-                        line.push(c)
-                    }, _ => {}
+        let mut editor = Editor::new(config_dir().map(|mut hist_file| {
+            hist_file.push("calc");
+            hist_file.push("history");
+            hist_file
+        }));
+        'repl: loop {
+            let mut line = match editor.read_line("> ", &env) {
+                None => exit(0),
+                Some(line) if is_quit(&line) => exit(0),
+                Some(line) if is_blank(&line) => continue,
+                Some(line) => line
+            };
+
+            // Keep reading until the statement is finished. Ctrl-C/Ctrl-D here just drops it
+            let mut parts = vec![ line.clone() ];
+            while !is_command(&line) && is_incomplete(&line) {
+                let helper = Continued { env: &env, before: format!("{}\n", line) };
+                match editor.read_line("... ", &helper) {
+                    None => continue 'repl,
+                    Some(more) => {
+                        line = format!("{}\n{}", line, more);
+                        parts.push(more);
+                    }
                 }
             }
 
-            let stmt = parse_stmt(line.as_str());
-            match stmt {
-                Err(err) => writeln!(out, "Error: {}", err).unwrap(),
-                Ok(ast) => writeln!(out, "{}", eval(&ast, &mut env)).unwrap()
+            // Don't bother remembering typos. History is one line per entry, so join the parts up,
+            // leaving out their comments so one can't swallow the parts after it
+            if is_command(&line) || parse_stmt(&line).is_ok() {
+                let entry = if parts.len() == 1 {
+                    String::from(line.trim())
+                } else {
+                    parts.iter()
+                        .map(|part| strip_comment(part).trim())
+                        .filter(|part| !part.is_empty())
+                        .collect::<Vec<&str>>().join(" ")
+                };
+                editor.add_history(&entry, env.settings.history_size);
+            }
+
+            match run_line(&line, &mut env) {
+                Err(err) => writeln!(out, "{}", err).unwrap(),
+                Ok(res) => writeln!(out, "{}", res).unwrap()
             }
         }
     }
 }
 
+// Whether the positional arg names a script rather than being statements itself. Just having a file
+// with that name around isn't enough, or 'c x' would run ./x, so it has to end in .calc or be a path
+// to an existing file, e.g. ./x (1/2 is only a path if there's a directory called 1)
+fn is_script(arg: &str) -> bool {
+    let path = Path::new(arg);
+    path.extension().is_some_and(|ext| ext == "calc") || (arg.contains(MAIN_SEPARATOR) && path.is_file())
+}
+
+// Lines as they come in, stopping at the first one that can't be read, e.g. if it isn't UTF-8
+// That gets reported on stderr and sets read_failed, so whatever came before still runs first
+fn read_lines<'a>(
+        reader: impl BufRead + 'a, source: &'a str, read_failed: &'a Cell<bool>) -> impl Iterator<Item = String> + 'a {
+    reader.lines().map_while(move |line| match line {
+        Ok(line) => Some(line),
+        Err(err) => {
+            eprintln!("{}: Error: Could not read line: {}", source, err);
+            read_failed.set(true);
+            None
+        }
+    })
+}
+
+// Group lines into whole statements, joining any that go on to the next line like an open '('
+// or a trailing '+'. Whatever's left unfinished at the end is still given back so it errors
+// Lines are only read as they're needed, so each statement can run before the next one arrives
+// Each comes with the line number it starts on, and blank or comment only lines are skipped
+fn join_continued(mut lines: impl Iterator<Item = String>) -> impl Iterator<Item = (usize, String)> {
+    let mut line_num = 0;
+    from_fn(move || loop {
+        let mut stmt = lines.next()?;
+        line_num += 1;
+        let start = line_num;
+        while !is_command(&stmt) && is_incomplete(&stmt) {
+            match lines.next() {
+                None => break,
+                Some(line) => {
+                    stmt = format!("{}\n{}", stmt, line);
+                    line_num += 1;
+                }
+            }
+        }
+        if !is_blank(&stmt) {
+            return Some((start, stmt));
+        }
+    })
+}
+
+// Either kind of error a statement can run into
+enum Failure {
+    Parse(ParseError),
+    Calc(CalcError)
+}
+
+// Run each statement, printing its result as text or JSON. Gives back false if anything failed
+// Errors in a file are reported as file:line:col on stderr, and without keep_going they stop everything
+fn run_stmts(
+        lines: impl Iterator<Item = String>, file: Option<&str>, keep_going: bool, json: bool,
+        env: &mut Environment) -> bool {
+    let mut ok = true;
+    let mut out = stdout().lock();
+    for (start, stmt) in join_continued(lines) {
+        if is_quit(&stmt) {
+            break;
+        }
+
+        let res = if is_command(&stmt) {
+            run_command(&stmt, env).map(|out| if json { command_json(&out) } else { out })
+                .map_err(Failure::Calc)
+        } else {
+            parse_stmt(&stmt).map_err(Failure::Parse)
+                .and_then(|ast| eval(&ast, env).map_err(Failure::Calc))
+                .map(|res| if json { outcome_json(&res) } else { res.to_string(&env.settings) })
+        };
+        let failure = match res {
+            Ok(res) => {
+                if let Err(err) = writeln!(out, "{}", res) {
+                    return write_failed(err) && ok;
+                }
+                continue;
+            }, Err(failure) => failure
+        };
+        ok = false;
+
+        // Parse errors know where they are, but otherwise point at the start of the statement
+        let (offset, msg) = match &failure {
+            Failure::Parse(err) => (err.position(&stmt), err.render(&stmt)),
+            Failure::Calc(err) => (stmt.len() - stmt.trim_start().len(), format!("Error: {}", err))
+        };
+        let before = stmt.split_at(offset.min(stmt.len())).0;
+        let line = start + before.matches('\n').count();
+        let col = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        let written = match (json, file) {
+            (true, _) => writeln!(out, "{}", match &failure {
+                Failure::Parse(err) => parse_error_json(err, line, col),
+                Failure::Calc(err) => calc_error_json(err, line, col)
+            }), (false, Some(file)) => {
+                eprintln!("{}:{}:{}: {}", file, line, col, msg);
+                Ok(())
+            }, (false, None) => writeln!(out, "{}", msg)
+        };
+        if let Err(err) = written {
+            return write_failed(err) && ok;
+        }
+
+        if !keep_going {
+            break;
+        }
+    }
+    ok
+}
+
+// Whatever's reading the results going away (e.g. 'c < exprs | head -1') just means it's seen
+// all it wants, so that stops things quietly. Gives back false for any other error
+fn write_failed(err: IoError) -> bool {
+    if err.kind() == ErrorKind::BrokenPipe {
+        true
+    } else {
+        eprintln!("Error: Could not write to stdout: {}", err);
+        false
+    }
+}
+
+// Run a command or statement, giving back the text to show for it either way
+fn run_line(line: &str, env: &mut Environment) -> Result<String, String> {
+    if is_command(line) {
+        return run_command(line, env).map_err(|err| format!("Error: {}", err));
+    }
+
+    match parse_stmt(line) {
+        Err(err) => Err(err.render(line)),
+        Ok(ast) => eval(&ast, env).map(|res| res.to_string(&env.settings))
+            .map_err(|err| format!("Error: {}", err))
+    }
+}
+
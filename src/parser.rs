@@ -2,8 +2,8 @@
  * Author: Dylan Turner
  * Description:
  * - Take string/repl input and turn it into an AST
- * - In order to let tests.rs access some functions, there are a few helpers that are public, but
- *   in use, just parse_stmt is used
+ * - parse_stmt is the main entry point, but the literal/identifier tokenizers are public too so
+ *   the repl can highlight input the same way it gets parsed
  */
 
 /*
@@ -12,20 +12,35 @@
  * <stmt>           ::= <expr> | <func-def> | <asgn>
  * <func-def>       ::= '\' <ident> '(' [ <ident> { ',' <ident> } ] ')' '->' <expr>
  * <asgn>           ::= 'let' <ident> ':=' <expr>
- * <expr>           ::= <un-expr> [ '?' <expr> ':' <expr> ]
- * <un-expr>        ::= <exp-expr> | 'j' <exp-expr> | '-' <exp-expr>
- * <exp-expr>       ::= <prod-expr> [ '^' <prod-expr> ]
- * <prod-expr>      ::= <sum-expr> [ ( '*' | '/' ) <sum-expr> ]
- * <sum-expr>       ::= <rel-expr> [ ( '+' | '-' ) <rel-expr> ]
- * <rel-expr>       ::= <term> [ ('=' | '=/=' | '>' | '<' | '>=' | '<=' ) <term> ]
+ * <expr>           ::= <or-expr> [ '?' <expr> ':' <expr> ]
+ * <or-expr>        ::= <xor-expr> { '|' <xor-expr> }
+ * <xor-expr>       ::= <and-expr> { 'xor' <and-expr> }
+ * <and-expr>       ::= <rel-expr> { '&' <rel-expr> }
+ * <rel-expr>       ::= <shift-expr> { ( '=' | '=/=' | '>' | '<' | '>=' | '<=' ) <shift-expr> }
+ * <shift-expr>     ::= <sum-expr> { ( '<<' | '>>' ) <sum-expr> }
+ * <sum-expr>       ::= <prod-expr> { ( '+' | '-' ) <prod-expr> }
+ * <prod-expr>      ::= <un-expr> { ( '*' | '/' ) <un-expr> }
+ * <un-expr>        ::= ( 'j' | '-' | '~' ) <un-expr> | <exp-expr>
+ * <exp-expr>       ::= <term> [ '^' <un-expr> ]
  * <term>           ::= <ident> | <float> | <int> | <list> | <func-call> | '(' <expr> ')'
  * <list>           ::= '[' [ <expr> { ',' <expr> } ] ']'
  * <func-call>      ::= <ident> '(' [ <expr> { ',' <expr> } ] ')'
  * <ident>          ::= /[A-Za-z_]+[A-Za-z_0-9]* /
  * <float>          ::= /([0-9]*\.)?[0-9]+([Ee]\-?[0-9]+)?/
- * <int>            ::= /[0-9]+_/
+ * <int>            ::= /[0-9]+_/ | /0x[0-9A-Fa-f]+_/ | /0o[0-7]+_/ | /0b[01]+_/
+ *
+ * The bitwise levels sit below the comparisons like in C, so 'x & 1 = 1' is 'x & (1 = 1)'
+ *
+ * Anywhere whitespace can go, so can a comment from '#' to the end of the line
  */
 
+use std::fmt::{
+    Display, Formatter, Result as FmtResult
+};
+use crate::error::{
+    ParseError, ParseErrorKind
+};
+
 #[derive(Clone, Debug)]
 pub enum Token {
     Statement(Box<Token>),
@@ -34,9 +49,13 @@ pub enum Token {
     Expression(Box<Token>, Option<Box<Token>>, Option<Box<Token>>),
     UnaryExpression(Box<Token>, Option<String>),
     ExponentialExpression(Box<Token>, Option<Box<Token>>),
-    ProductExpression(Box<Token>, Option<String>, Option<Box<Token>>),
-    SumExpression(Box<Token>, Option<String>, Option<Box<Token>>),
-    RelationalExpression(Box<Token>, Option<String>, Option<Box<Token>>),
+    ProductExpression(Box<Token>, Vec<(String, Box<Token>)>),
+    SumExpression(Box<Token>, Vec<(String, Box<Token>)>),
+    ShiftExpression(Box<Token>, Vec<(String, Box<Token>)>),
+    RelationalExpression(Box<Token>, Vec<(String, Box<Token>)>),
+    AndExpression(Box<Token>, Vec<(String, Box<Token>)>),
+    XorExpression(Box<Token>, Vec<(String, Box<Token>)>),
+    OrExpression(Box<Token>, Vec<(String, Box<Token>)>),
     Term(Box<Token>),
     Identifier(String),
     Number(String),
@@ -54,778 +73,809 @@ pub struct ParseResult {
     pub token: Token
 }
 
-// Primary parsing function:
-// <stmt> ::= <asgn> | <func-def> | <expr>
-pub fn parse_stmt(code: &str) -> Result<Token, String> {
-    let attempt = parse_func_def(code);
-    if attempt.is_some() {
-        if attempt.clone().unwrap().new_start < code.len() {
-            return Err(format!(
-                "Extra characters at end of expression starting at {}",
-                attempt.unwrap().new_start
-            ))
-        } else {
-            return Ok(Token::Statement(Box::new(attempt.unwrap().token)));
+// First operand, the (op, operand) pairs that follow it, and the new start
+type ChainResult = (Token, Vec<(String, Box<Token>)>, usize);
+
+// Turn a tree back into source that parses to the same thing, e.g. for showing function bodies
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let join = |items: &[Box<Token>]| items.iter()
+            .map(|item| item.to_string()).collect::<Vec<String>>().join(", ");
+        match self {
+            Token::Statement(stmt) => write!(f, "{}", stmt),
+            Token::FunctionDefinition(name, args, expr) => write!(
+                f, "\\{}({}) -> {}", name, args.join(", "), expr
+            ), Token::Assignment(name, expr) => write!(f, "let {} := {}", name, expr),
+            Token::Expression(cond, Some(t), Some(e)) => write!(f, "{} ? {} : {}", cond, t, e),
+            Token::Expression(inner, _, _)
+                | Token::UnaryExpression(inner, None)
+                | Token::ExponentialExpression(inner, None) => write!(f, "{}", inner),
+            Token::UnaryExpression(inner, Some(op)) => write!(f, "{}{}", op, inner),
+            Token::ExponentialExpression(base, Some(power)) => write!(f, "{}^{}", base, power),
+            Token::ProductExpression(first, rest)
+                | Token::SumExpression(first, rest)
+                | Token::ShiftExpression(first, rest)
+                | Token::RelationalExpression(first, rest)
+                | Token::AndExpression(first, rest)
+                | Token::XorExpression(first, rest)
+                | Token::OrExpression(first, rest) => {
+                write!(f, "{}", first)?;
+                for (op, operand) in rest {
+                    write!(f, " {} {}", op, operand)?;
+                }
+                Ok(())
+            }, Token::Term(inner) => match inner.as_ref() {
+                // Only bracketed expressions get a whole expression as a term
+                Token::Expression(..) => write!(f, "({})", inner),
+                inner => write!(f, "{}", inner)
+            }, Token::Identifier(text) | Token::Number(text) | Token::Integer(text)
+                | Token::Word(text) => write!(f, "{}", text),
+            Token::List(items) => write!(f, "[{}]", join(items)),
+            Token::FunctionCall(name, args) => write!(f, "{}({})", name, join(args)),
+            Token::Whitespace => write!(f, " ")
         }
     }
-    
-    let attempt = parse_asgn(code);
-    if attempt.is_some() {
-        if attempt.clone().unwrap().new_start < code.len() {
-            return Err(format!(
-                "Extra characters at end of expression starting at {}",
-                attempt.unwrap().new_start
-            ))
-        } else {
-            return Ok(Token::Statement(Box::new(attempt.unwrap().token)));
-        }
+}
+
+// Primary parsing function:
+// <stmt> ::= <asgn> | <func-def> | <expr>
+pub fn parse_stmt(code: &str) -> Result<Token, ParseError> {
+    // Leading whitespace is fine, so start after it
+    let pos = parse_whitespace(code).new_start;
+    let body = code.split_at(pos).1;
+
+    let stmt = if let Some(def) = parse_func_def(body, pos)? {
+        def
+    } else if let Some(asgn) = parse_asgn(body, pos)? {
+        asgn
+    } else {
+        parse_expr(body, pos)?
+    };
+
+    let end = pos + stmt.new_start;
+    if end < code.len() {
+        let extra = code.split_at(end).1.trim_end();
+        Err(ParseError::new(
+            ParseErrorKind::ExtraCharacters(String::from(extra)),
+            (end, end + extra.len()),
+            &[ "an operator", "end of input" ]
+        ))
+    } else {
+        Ok(Token::Statement(Box::new(stmt.token)))
     }
+}
 
-    match parse_expr(code, 0) {
-        Err(err) => Err(err),
-        Ok(expr) => if expr.new_start < code.len() {
-            Err(format!("Extra characters at end of expression starting at {}", expr.new_start))
-        } else {
-            Ok(Token::Statement(Box::new(expr.token)))
-        }
+// Whether the code stops partway through a statement, like an open '(' or a trailing '+',
+// meaning the next line should be read as more of it instead of running it as is
+pub fn is_incomplete(code: &str) -> bool {
+    if is_blank(code) {
+        return false;
+    }
+    match parse_stmt(code) {
+        Err(err) => err.span.0 >= code.trim_end().len() && matches!(
+            err.kind, ParseErrorKind::UnexpectedEnd | ParseErrorKind::UnclosedDelimiter(_)
+        ), Ok(_) => false
     }
 }
 
+// Nothing but whitespace and comments, so there's nothing to run
+pub fn is_blank(code: &str) -> bool {
+    parse_whitespace(code).new_start == code.len()
+}
+
+// A line without its comment, so lines can be joined up without the comment swallowing the rest
+pub fn strip_comment(line: &str) -> &str {
+    line.split_once('#').map_or(line, |(code, _)| code)
+}
+
 /* Helpers for each statement */
 
-// <func-def> ::= '\' <ident> '(' [ <ident> { ',' <ident> } ] ')' '=' <expr>
-fn parse_func_def(code: &str) -> Option<ParseResult> {
+// <func-def> ::= '\' <ident> '(' [ <ident> { ',' <ident> } ] ')' '->' <expr>
+// Not a function definition at all gives Ok(None), but once we see '\' any problem is an error
+fn parse_func_def(code: &str, pos: usize) -> Result<Option<ParseResult>, ParseError> {
     let mut substr_start;
 
     // '\'
-    let lambda = parse_word("\\", code);
-    if lambda.is_none() {
-        return None;
+    match parse_word("\\", code) {
+        None => return Ok(None),
+        Some(lambda) => substr_start = lambda.new_start
     }
-    substr_start = lambda.unwrap().new_start;
 
-    let name = parse_ident(code.split_at(substr_start).1);
-    if name.is_none() {
-        return None;
-    }
-    substr_start += name.clone().unwrap().new_start;
-    let name_str = if let Token::Identifier(try_name_str) = name.unwrap().token {
-        try_name_str
-    } else {
-        String::new()
-    };
+    let name = expect_ident(code.split_at(substr_start).1, pos + substr_start, "function name")?;
+    substr_start += name.new_start;
+    let name_str = ident_str(name.token);
 
     // '('
-    let par = parse_word("(", code.split_at(substr_start).1);
-    if par.is_none() {
-        return None;
-    }
-    substr_start += par.unwrap().new_start;
+    substr_start += expect_word("(", code.split_at(substr_start).1, pos + substr_start)?;
 
-    // <ident> { ',' <ident> } ]
+    // [ <ident> { ',' <ident> } ]
     let mut args = Vec::new();
-    let try_arg = parse_ident(code.split_at(substr_start).1);
-    if try_arg.is_some() {
-        substr_start += try_arg.clone().unwrap().new_start;
-        args.push(
-            if let Token::Identifier(try_arg_str) = try_arg.unwrap().token {
-                try_arg_str
-            } else {
-                String::new()
-            }
-        );
+    if let Some(arg) = parse_ident(code.split_at(substr_start).1) {
+        substr_start += arg.new_start;
+        args.push(ident_str(arg.token));
 
-        loop {
-            let comma = parse_word(",", code.split_at(substr_start).1);
-            if comma.is_none() {
-                break;
-            }
-            substr_start += comma.unwrap().new_start;
+        while let Some(comma) = parse_word(",", code.split_at(substr_start).1) {
+            substr_start += comma.new_start;
 
-            let arg = parse_ident(code.split_at(substr_start).1);
-            if arg.is_none() {
-                return None;
-            }
-            substr_start += arg.clone().unwrap().new_start;
-            args.push(
-                if let Token::Identifier(arg_str) = arg.unwrap().token {
-                    arg_str
-                } else {
-                    String::new()
-                }
-            );
+            let arg = expect_ident(code.split_at(substr_start).1, pos + substr_start, "argument name")?;
+            substr_start += arg.new_start;
+            args.push(ident_str(arg.token));
         }
     }
 
     // ')'
-    let par = parse_word(")", code.split_at(substr_start).1);
-    if par.is_none() {
-        return None;
+    match parse_word(")", code.split_at(substr_start).1) {
+        None => return Err(unexpected(
+            code.split_at(substr_start).1, pos + substr_start, &[ "argument name", "')'" ]
+        )), Some(par) => substr_start += par.new_start
     }
-    substr_start += par.unwrap().new_start;
 
     // '->'
-    let eq = parse_word("->", code.split_at(substr_start).1);
-    if eq.is_none() {
-        return None;
-    }
-    substr_start += eq.unwrap().new_start;
+    substr_start += expect_word("->", code.split_at(substr_start).1, pos + substr_start)?;
 
-    let expr = parse_expr(code.split_at(substr_start).1, substr_start);
-    if expr.is_err() {
-        return None;
-    }
-    substr_start += expr.clone().unwrap().new_start;
+    let expr = parse_expr(code.split_at(substr_start).1, pos + substr_start)?;
+    substr_start += expr.new_start;
 
-    Some(ParseResult {
+    Ok(Some(ParseResult {
         new_start: substr_start,
-        token: Token::FunctionDefinition(
-            name_str.clone(), args, Box::new(expr.unwrap().token)
-        )
-    })
+        token: Token::FunctionDefinition(name_str, args, Box::new(expr.token))
+    }))
 }
 
 // <asgn> ::= 'let' <ident> ':=' <expr>
-fn parse_asgn(code: &str) -> Option<ParseResult> {
+// Same as func defs, Ok(None) means it's not an assignment, but after 'let' it has to be one
+fn parse_asgn(code: &str, pos: usize) -> Result<Option<ParseResult>, ParseError> {
     let mut substr_start;
 
     // 'let'
-    let keyword = parse_word("let", code);
-    if keyword.is_none() {
-        return None;
+    match parse_keyword("let", code) {
+        None => return Ok(None),
+        Some(keyword) => substr_start = keyword.new_start
     }
-    substr_start = keyword.unwrap().new_start;
 
-    let name = parse_ident(code.split_at(substr_start).1);
-    if name.is_none() {
-        return None;
-    }
-    substr_start += name.clone().unwrap().new_start;
-    let name_str = if let Token::Identifier(try_name_str) = name.unwrap().token {
-        try_name_str
-    } else {
-        String::new()
-    };
+    let name = expect_ident(code.split_at(substr_start).1, pos + substr_start, "variable name")?;
+    substr_start += name.new_start;
+    let name_str = ident_str(name.token);
 
-    // '='
-    let eq = parse_word("=", code.split_at(substr_start).1);
-    if eq.is_none() {
-        return None;
+    // ':=' (plain '=' also accepted for older init files)
+    match parse_word(":=", code.split_at(substr_start).1)
+            .or_else(|| parse_word("=", code.split_at(substr_start).1)) {
+        None => return Err(unexpected(
+            code.split_at(substr_start).1, pos + substr_start, &[ "':='" ]
+        )), Some(eq) => substr_start += eq.new_start
     }
-    substr_start += eq.unwrap().new_start;
 
-    let expr = parse_expr(code.split_at(substr_start).1, substr_start);
-    if expr.is_err() {
-        return None;
-    }
-    substr_start += expr.clone().unwrap().new_start;
+    let expr = parse_expr(code.split_at(substr_start).1, pos + substr_start)?;
+    substr_start += expr.new_start;
 
-    Some(ParseResult {
+    Ok(Some(ParseResult {
         new_start: substr_start,
-        token: Token::Assignment(
-            name_str.clone(), Box::new(expr.unwrap().token)
-        )
-    })
+        token: Token::Assignment(name_str, Box::new(expr.token))
+    }))
 }
 
-/* Expressionession Parser */
-
-// <expr> ::= <un-expr> [ '?' <expr> ':' <expr> ]
-fn parse_expr(code: &str, pos: usize) -> Result<ParseResult, String> {
-    let unary = parse_un_expr(code, pos);
-    match unary {
-        Err(err) => Err(err),
-        Ok(unary_res) => {
-            let q = parse_word("?", code.split_at(unary_res.new_start).1);
-            if q.is_none() {
-                Ok(ParseResult {
-                    new_start: unary_res.new_start,
-                    token: Token::Expression(Box::new(unary_res.token), None, None)
-                })
-            } else {
-                let mut substr_start = unary_res.new_start + q.unwrap().new_start;
+/* Expression Parser */
 
-                let t_expr = parse_expr(code.split_at(substr_start).1, pos + substr_start);
-                if t_expr.is_err() {
-                    return Err(format!(
-                        "Expected expression after '?' at pos {}", pos + substr_start
-                    ));
-                }
-                substr_start += t_expr.clone().unwrap().new_start;
+// <expr> ::= <or-expr> [ '?' <expr> ':' <expr> ]
+fn parse_expr(code: &str, pos: usize) -> Result<ParseResult, ParseError> {
+    let cond = parse_or_expr(code, pos)?;
 
-                let coln = parse_word(":", code.split_at(substr_start).1);
-                if coln.is_none() {
-                    return Err(format!("Expected ':' at pos {}", pos + substr_start));
-                }
-                substr_start += coln.unwrap().new_start;
+    let q = match parse_word("?", code.split_at(cond.new_start).1) {
+        None => return Ok(ParseResult {
+            new_start: cond.new_start,
+            token: Token::Expression(Box::new(cond.token), None, None)
+        }), Some(q) => q
+    };
+    let mut substr_start = cond.new_start + q.new_start;
 
-                let f_expr = parse_expr(code.split_at(substr_start).1, pos + substr_start);
-                if f_expr.is_err() {
-                    return Err(format!(
-                        "Expected expression after ':' at pos {}", pos + substr_start
-                    ));
-                }
-                substr_start += f_expr.clone().unwrap().new_start;
-
-                Ok(ParseResult {
-                    new_start: substr_start,
-                    token: Token:: Expression(
-                        Box::new(unary_res.token),
-                        Some(Box::new(t_expr.unwrap().token)),
-                        Some(Box::new(f_expr.unwrap().token))
-                    )
-                })
-            }
-        }
-    }
-}
+    let t_expr = parse_expr(code.split_at(substr_start).1, pos + substr_start)?;
+    substr_start += t_expr.new_start;
 
-// <un-expr> ::= <exp-expr> | 'j' <exp-expr> | '-' <exp-expr>
-fn parse_un_expr(code: &str, pos: usize) -> Result<ParseResult, String> {
-    let mut substr_start = 0;
+    substr_start += expect_word(":", code.split_at(substr_start).1, pos + substr_start)?;
 
-    let ops = [ "j", "-" ];
-    let mut atmpt = None;
-    let mut used_op = "";
-    for op in ops {
-        atmpt = parse_word(op, code);
-        if atmpt.is_some() {
-            used_op = op;
-            break;
-        }
-    }
-    if atmpt.is_some() {
-        substr_start = atmpt.clone().unwrap().new_start;
-    }
-
-    let exp = parse_exp_expr(code.split_at(substr_start).1, pos + substr_start);
-    if exp.is_err() {
-        return Err(exp.err().unwrap());
-    }
-    substr_start += exp.clone().unwrap().new_start;
+    let f_expr = parse_expr(code.split_at(substr_start).1, pos + substr_start)?;
+    substr_start += f_expr.new_start;
 
-    return Ok(ParseResult {
+    Ok(ParseResult {
         new_start: substr_start,
-        token: Token::UnaryExpression(
-            Box::new(exp.unwrap().token),
-            if atmpt.is_some() {
-                Some(String::from(used_op))
-            } else {
-                None
-            }
+        token: Token::Expression(
+            Box::new(cond.token), Some(Box::new(t_expr.token)), Some(Box::new(f_expr.token))
         )
     })
 }
 
-// <exp-expr> ::= <prod-expr> [ '^' <prod-expr> ]
-fn parse_exp_expr(code: &str, pos: usize) -> Result<ParseResult, String> {
-    let mut substr_start;
+// <or-expr> ::= <xor-expr> { '|' <xor-expr> }
+fn parse_or_expr(code: &str, pos: usize) -> Result<ParseResult, ParseError> {
+    let (first, rest, new_start) = parse_chain(code, pos, &[ "|" ], parse_xor_expr)?;
+    Ok(ParseResult {
+        new_start,
+        token: Token::OrExpression(Box::new(first), rest)
+    })
+}
 
-    let fst = parse_prod_expr(code, pos);
-    if fst.is_err() {
-        return Err(fst.err().unwrap());
-    }
-    substr_start = fst.clone().unwrap().new_start;
+// <xor-expr> ::= <and-expr> { 'xor' <and-expr> }
+// A word instead of '^', since that's already power
+fn parse_xor_expr(code: &str, pos: usize) -> Result<ParseResult, ParseError> {
+    let (first, rest, new_start) = parse_chain(code, pos, &[ "xor" ], parse_and_expr)?;
+    Ok(ParseResult {
+        new_start,
+        token: Token::XorExpression(Box::new(first), rest)
+    })
+}
 
-    let atmpt = parse_word("^", code.split_at(substr_start).1);
-    if atmpt.is_none() {
-        return Ok(ParseResult {
-            new_start: fst.clone().unwrap().new_start,
-            token: Token::ExponentialExpression(Box::new(fst.unwrap().token), None)
-        });
-    }
-    substr_start += atmpt.unwrap().new_start;
+// <and-expr> ::= <rel-expr> { '&' <rel-expr> }
+fn parse_and_expr(code: &str, pos: usize) -> Result<ParseResult, ParseError> {
+    let (first, rest, new_start) = parse_chain(code, pos, &[ "&" ], parse_rel_expr)?;
+    Ok(ParseResult {
+        new_start,
+        token: Token::AndExpression(Box::new(first), rest)
+    })
+}
 
-    // We found the operator, let's get the next token
-    let snd = parse_prod_expr(code.split_at(substr_start).1, pos + substr_start);
-    if snd.is_err() {
-        return Ok(ParseResult {
-            new_start: fst.clone().unwrap().new_start,
-            token: Token::ExponentialExpression(Box::new(fst.unwrap().token), None)
-        });
-    }
-    substr_start += snd.clone().unwrap().new_start;
+// <rel-expr> ::= <shift-expr> { ( '=' | '=/=' | '>' | '<' | '>=' | '<=' ) <shift-expr> }
+fn parse_rel_expr(code: &str, pos: usize) -> Result<ParseResult, ParseError> {
+    // Longer operators first so '>=' isn't read as '>' followed by garbage
+    let (first, rest, new_start) = parse_chain(
+        code, pos, &[ "=/=", "=", ">=", "<=", ">", "<" ], parse_shift_expr
+    )?;
+    Ok(ParseResult {
+        new_start,
+        token: Token::RelationalExpression(Box::new(first), rest)
+    })
+}
 
-    return Ok(ParseResult {
-        new_start: substr_start,
-        token: Token::ExponentialExpression(
-            Box::new(fst.unwrap().token),
-            Some(Box::new(snd.unwrap().token))
-        )
+// <shift-expr> ::= <sum-expr> { ( '<<' | '>>' ) <sum-expr> }
+fn parse_shift_expr(code: &str, pos: usize) -> Result<ParseResult, ParseError> {
+    let (first, rest, new_start) = parse_chain(code, pos, &[ "<<", ">>" ], parse_sum_expr)?;
+    Ok(ParseResult {
+        new_start,
+        token: Token::ShiftExpression(Box::new(first), rest)
     })
 }
 
-// <prod-expr> ::= <sum-expr> [ ( '*' | '/' ) <sum-expr> ]
-fn parse_prod_expr(code: &str, pos: usize) -> Result<ParseResult, String> {
-    let mut substr_start;
+// <sum-expr> ::= <prod-expr> { ( '+' | '-' ) <prod-expr> }
+fn parse_sum_expr(code: &str, pos: usize) -> Result<ParseResult, ParseError> {
+    let (first, rest, new_start) = parse_chain(code, pos, &[ "+", "-" ], parse_prod_expr)?;
+    Ok(ParseResult {
+        new_start,
+        token: Token::SumExpression(Box::new(first), rest)
+    })
+}
 
-    let fst = parse_sum_expr(code, pos);
-    if fst.is_err() {
-        return Err(fst.err().unwrap());
-    }
-    substr_start = fst.clone().unwrap().new_start;
+// <prod-expr> ::= <un-expr> { ( '*' | '/' ) <un-expr> }
+fn parse_prod_expr(code: &str, pos: usize) -> Result<ParseResult, ParseError> {
+    let (first, rest, new_start) = parse_chain(code, pos, &[ "*", "/" ], parse_un_expr)?;
+    Ok(ParseResult {
+        new_start,
+        token: Token::ProductExpression(Box::new(first), rest)
+    })
+}
 
-    let ops = [ "*", "/" ];
-    let mut atmpt = None;
-    let mut used_op = "";
-    for op in ops {
-        atmpt = parse_word(op, code.split_at(substr_start).1);
-        if atmpt.is_some() {
-            used_op = op;
-            break;
+// <un-expr> ::= ( 'j' | '-' | '~' ) <un-expr> | <exp-expr>
+fn parse_un_expr(code: &str, pos: usize) -> Result<ParseResult, ParseError> {
+    for op in [ "j", "-", "~" ] {
+        if let Some(atmpt) = parse_word(op, code) {
+            let substr_start = atmpt.new_start;
+            let inner = parse_un_expr(code.split_at(substr_start).1, pos + substr_start)?;
+            return Ok(ParseResult {
+                new_start: substr_start + inner.new_start,
+                token: Token::UnaryExpression(Box::new(inner.token), Some(String::from(op)))
+            });
         }
     }
-    if atmpt.is_none() {
-        return Ok(ParseResult {
-            new_start: fst.clone().unwrap().new_start,
-            token: Token::ProductExpression(Box::new(fst.unwrap().token), None, None)
-        });
-    }
-    substr_start += atmpt.unwrap().new_start;
 
-    // We found the operator, let's get the next token
-    let snd = parse_sum_expr(code.split_at(substr_start).1, pos + substr_start);
-    if snd.is_err() {
-        return Ok(ParseResult {
-            new_start: fst.clone().unwrap().new_start,
-            token: Token::ProductExpression(Box::new(fst.unwrap().token), None, None)
-        });
-    }
-    substr_start += snd.clone().unwrap().new_start;
-
-    return Ok(ParseResult {
-        new_start: substr_start,
-        token: Token::ProductExpression(
-            Box::new(fst.unwrap().token),
-            Some(String::from(used_op)),
-            Some(Box::new(snd.unwrap().token))
-        )
+    let exp = parse_exp_expr(code, pos)?;
+    Ok(ParseResult {
+        new_start: exp.new_start,
+        token: Token::UnaryExpression(Box::new(exp.token), None)
     })
 }
 
-// <sum-expr> ::= <rel-expr> [ ( '+' | '-' ) <rel-expr> ]
-fn parse_sum_expr(code: &str, pos: usize) -> Result<ParseResult, String> {
-    let mut substr_start;
-
-    let fst = parse_rel_expr(code, pos);
-    if fst.is_err() {
-        return Err(fst.err().unwrap());
-    }
-    substr_start = fst.clone().unwrap().new_start;
-
-    let ops = [ "+", "-" ];
-    let mut atmpt = None;
-    let mut used_op = "";
-    for op in ops {
-        atmpt = parse_word(op, code.split_at(substr_start).1);
-        if atmpt.is_some() {
-            used_op = op;
-            break;
-        }
-    }
-    if atmpt.is_none() {
-        return Ok(ParseResult {
-            new_start: fst.clone().unwrap().new_start,
-            token: Token::SumExpression(Box::new(fst.unwrap().token), None, None)
-        });
-    }
-    substr_start += atmpt.unwrap().new_start;
+// <exp-expr> ::= <term> [ '^' <un-expr> ]
+// Recursing into <un-expr> on the right makes '^' right-associative and allows 2^-1
+fn parse_exp_expr(code: &str, pos: usize) -> Result<ParseResult, ParseError> {
+    let base = parse_term(code, pos)?;
+    let mut substr_start = base.new_start;
+
+    let caret = match parse_word("^", code.split_at(substr_start).1) {
+        None => return Ok(ParseResult {
+            new_start: substr_start,
+            token: Token::ExponentialExpression(Box::new(base.token), None)
+        }), Some(caret) => caret
+    };
+    substr_start += caret.new_start;
 
-    // We found the operator, let's get the next token
-    let snd = parse_rel_expr(code.split_at(substr_start).1, pos + substr_start);
-    if snd.is_err() {
-        return Ok(ParseResult {
-            new_start: fst.clone().unwrap().new_start,
-            token: Token::SumExpression(Box::new(fst.unwrap().token), None, None)
-        });
-    }
-    substr_start += snd.clone().unwrap().new_start;
+    let power = parse_un_expr(code.split_at(substr_start).1, pos + substr_start)?;
+    substr_start += power.new_start;
 
-    return Ok(ParseResult {
+    Ok(ParseResult {
         new_start: substr_start,
-        token: Token::SumExpression(
-            Box::new(fst.unwrap().token),
-            Some(String::from(used_op)),
-            Some(Box::new(snd.unwrap().token))
-        )
+        token: Token::ExponentialExpression(Box::new(base.token), Some(Box::new(power.token)))
     })
 }
 
-// <rel-expr> ::= <term> [ ('=' | '=/=' | '>' | '<' | '>=' | '<=' ) <term> ]
-fn parse_rel_expr(code: &str, pos: usize) -> Result<ParseResult, String> {
-    let mut substr_start;
-
-    let first = parse_term(code, pos);
-    if first.is_err() {
-        return Err(first.err().unwrap());
-    }
-    substr_start = first.clone().unwrap().new_start;
-
-    // Optional '==', '=/=', '>', ...
-    let ops = [ "=/=", "=", ">=", "<=", ">", "<" ];
-    let mut atmpt = None;
-    let mut used_op = "";
-    for op in ops {
-        atmpt = parse_word(op, code.split_at(substr_start).1);
-        if atmpt.is_some() {
-            used_op = op;
-            break;
-        }
-    }
-    if atmpt.is_none() {
-        return Ok(ParseResult {
-            new_start: first.clone().unwrap().new_start,
-            token: Token::RelationalExpression(Box::new(first.unwrap().token), None, None)
+// Shared by the left-associative levels: <sub> { <op> <sub> }
+// Returns the first operand, every (op, operand) pair after it, and where parsing stopped
+// Word operators like 'xor' can't just be the start of a name
+fn parse_chain(
+        code: &str, pos: usize, ops: &[&str],
+        sub: fn(&str, usize) -> Result<ParseResult, ParseError>) -> Result<ChainResult, ParseError> {
+    let first = sub(code, pos)?;
+    let mut substr_start = first.new_start;
+    let mut rest = Vec::new();
+
+    loop {
+        let found = ops.iter().find_map(|op| {
+            let after = code.split_at(substr_start).1;
+            let atmpt = if op.starts_with(char::is_alphabetic) {
+                parse_keyword(op, after)
+            } else {
+                parse_word(op, after)
+            };
+            atmpt.map(|atmpt| (*op, atmpt.new_start))
         });
-    }
-    substr_start += atmpt.unwrap().new_start;
+        let (op, op_len) = match found {
+            None => break,
+            Some(found) => found
+        };
+        substr_start += op_len;
 
-    // We found the operator, let's get the next token
-    let snd = parse_term(code.split_at(substr_start).1, pos + substr_start);
-    if snd.is_err() {
-        return Ok(ParseResult {
-            new_start: first.clone().unwrap().new_start,
-            token: Token::RelationalExpression(Box::new(first.unwrap().token), None, None)
-        });
+        let operand = sub(code.split_at(substr_start).1, pos + substr_start)?;
+        substr_start += operand.new_start;
+        rest.push((String::from(op), Box::new(operand.token)));
     }
-    substr_start += snd.clone().unwrap().new_start;
 
-    return Ok(ParseResult {
-        new_start: substr_start,
-        token: Token::RelationalExpression(
-            Box::new(first.unwrap().token),
-            Some(String::from(used_op)),
-            Some(Box::new(snd.unwrap().token))
-        )
-    })
+    Ok((first.token, rest, substr_start))
 }
 
 // <term> ::= <ident> | <float> | <int> | <list> | <func-call> | '(' <expr> ')'
-fn parse_term(code: &str, pos: usize) -> Result<ParseResult, String> {
+fn parse_term(code: &str, pos: usize) -> Result<ParseResult, ParseError> {
     // Check for parenth
-    let par = parse_word("(", code);
-    if par.is_some() {
+    if let Some(par) = parse_word("(", code) {
         // '(' <expr> ')'
-        let mut substr_start = par.unwrap().new_start;
-        
-        let sub_expr = parse_expr(code.split_at(substr_start).1, pos + substr_start);
-        if sub_expr.is_err() {
-            return Err(sub_expr.err().unwrap());
-        }
-        substr_start += sub_expr.clone().unwrap().new_start;
-
-        let par = parse_word(")", code.split_at(substr_start).1);
-        if par.is_none() {
-            return Err(format!("Missing ')' at pos {}", substr_start + pos));
-        } else {
-           return Ok(ParseResult {
-                new_start: substr_start + par.unwrap().new_start,
-                token: Token::Term(Box::new(sub_expr.unwrap().token))
-            });
-        }
-    }
+        let mut substr_start = par.new_start;
 
-    let atmpt = parse_list(code, pos);
-    if atmpt.is_some() {
-        return Ok(ParseResult {
-            new_start: atmpt.clone().unwrap().new_start,
-            token: Token::Term(Box::new(atmpt.unwrap().token))
-        });
-    }
+        let sub_expr = parse_expr(code.split_at(substr_start).1, pos + substr_start)?;
+        substr_start += sub_expr.new_start;
 
-    let atmpt = parse_func_call(code, pos);
-    if atmpt.is_some() {
-        return Ok(ParseResult {
-            new_start: atmpt.clone().unwrap().new_start,
-            token: Token::Term(Box::new(atmpt.unwrap().token))
-        });
+        return match parse_word(")", code.split_at(substr_start).1) {
+            None => Err(unclosed('(', code.split_at(substr_start).1, pos + substr_start, &[ "')'" ])),
+            Some(par) => Ok(ParseResult {
+                new_start: substr_start + par.new_start,
+                token: Token::Term(Box::new(sub_expr.token))
+            })
+        };
     }
 
-    let atmpt = parse_ident(code);
-    if atmpt.is_some() {
+    let atmpt = match parse_list(code, pos)? {
+        Some(list) => Some(list),
+        None => parse_func_call(code, pos)?
+    }.or_else(|| parse_ident(code));
+    if let Some(atmpt) = atmpt {
         return Ok(ParseResult {
-            new_start: atmpt.clone().unwrap().new_start,
-            token: Token::Term(Box::new(atmpt.unwrap().token))
+            new_start: atmpt.new_start,
+            token: Token::Term(Box::new(atmpt.token))
         });
     }
 
-    let atmpt = parse_integer(code);
-    if atmpt.is_some() {
-        return Ok(ParseResult {
-            new_start: atmpt.clone().unwrap().new_start,
-            token: Token::Term(Box::new(atmpt.unwrap().token))
-        });
-    }
+    if let Some(atmpt) = parse_integer(code).or_else(|| parse_number(code)) {
+        // Catch things like '2x' or '1.2.3' here instead of as confusing extra characters
+        let lexeme = next_lexeme(code);
+        if code.split_at(atmpt.new_start).0.trim_end().len() < lexeme.len() {
+            return Err(ParseError::new(
+                ParseErrorKind::InvalidLiteral(String::from(lexeme)),
+                (pos, pos + lexeme.len()),
+                &[]
+            ));
+        }
 
-    let atmpt = parse_number(code);
-    if atmpt.is_some() {
         return Ok(ParseResult {
-            new_start: atmpt.clone().unwrap().new_start,
-            token: Token::Term(Box::new(atmpt.unwrap().token))
+            new_start: atmpt.new_start,
+            token: Token::Term(Box::new(atmpt.token))
         });
     }
 
-    Err(format!("Expected term at pos {}", pos))
+    Err(unexpected(code, pos, &[ "a number", "an identifier", "'('", "'['" ]))
 }
 
 /* Complex terms (i.e. uses base terms, but not quite into actual expr building yet) */
 
 // <list> ::= '[' [ <expr> { ',' <expr> } ] ']'
-fn parse_list(code: &str, pos: usize) -> Option<ParseResult> {
-    let mut items = Vec::new();
-    let mut substr_start;
-
-    let brack = parse_word("[", code);
-    if brack.is_none() {
-        return None;
-    }
-    substr_start = brack.unwrap().new_start;
-
-    let first_item = parse_expr(code.split_at(substr_start).1, pos + substr_start);
-    if first_item.is_ok() {
-        items.push(Box::new(first_item.clone().unwrap().token));
-        substr_start += first_item.unwrap().new_start;
-
-        loop {
-            let comma = parse_word(",", code.split_at(substr_start).1);
-            if comma.is_none() {
-                break;
-            }
-            substr_start += comma.unwrap().new_start;
-
-            let item = parse_expr(code.split_at(substr_start).1, pos + substr_start);
-            if item.is_err() {
-                return None;
-            }
-            items.push(Box::new(item.clone().unwrap().token));
-            substr_start += item.unwrap().new_start;
-        }
-    }
-
-    let brack = parse_word("]", code.split_at(substr_start).1);
-    if brack.is_none() {
-        return None;
-    }
-    substr_start += brack.unwrap().new_start;
+fn parse_list(code: &str, pos: usize) -> Result<Option<ParseResult>, ParseError> {
+    let substr_start = match parse_word("[", code) {
+        None => return Ok(None),
+        Some(brack) => brack.new_start
+    };
 
-    Some(ParseResult {
-        new_start: substr_start,
+    let (items, new_start) = parse_expr_list('[', "]", code, substr_start, pos)?;
+    Ok(Some(ParseResult {
+        new_start,
         token: Token::List(items)
-    })
+    }))
 }
 
 // <func-call> ::= <ident> '(' [ <expr> { ',' <expr> } ] ')'
-fn parse_func_call(code: &str, pos: usize) -> Option<ParseResult> {
-    let mut substr_start;
-    let mut args = Vec::new();
-
-    let fname = parse_ident(code);
-    if fname.is_none() {
-        return None;
-    }
-    substr_start = fname.clone().unwrap().new_start;
-    let fname_str = if let Token::Identifier(try_fname_str) = fname.unwrap().token {
-        try_fname_str
-    } else {
-        String::new()
+fn parse_func_call(code: &str, pos: usize) -> Result<Option<ParseResult>, ParseError> {
+    let fname = match parse_ident(code) {
+        None => return Ok(None),
+        Some(fname) => fname
     };
+    let mut substr_start = fname.new_start;
 
     // '('
-    let par = parse_word("(", code.split_at(substr_start).1);
-    if par.is_none() {
-        return None;
+    match parse_word("(", code.split_at(substr_start).1) {
+        None => return Ok(None),
+        Some(par) => substr_start += par.new_start
     }
-    substr_start += par.unwrap().new_start;
 
-    // Expression list
-    let first_arg = parse_expr(code.split_at(substr_start).1, pos + substr_start);
-    if first_arg.is_ok() {
-        args.push(Box::new(first_arg.clone().unwrap().token));
-        substr_start += first_arg.unwrap().new_start;
+    let (args, new_start) = parse_expr_list('(', ")", code, substr_start, pos)?;
+    Ok(Some(ParseResult {
+        new_start,
+        token: Token::FunctionCall(ident_str(fname.token), args)
+    }))
+}
 
-        loop {
-            let comma = parse_word(",", code.split_at(substr_start).1);
-            if comma.is_none() {
-                break;
-            }
-            substr_start += comma.unwrap().new_start;
+// Shared by lists and calls: [ <expr> { ',' <expr> } ] <close>, starting just after the opener
+fn parse_expr_list(
+        open: char, close: &str, code: &str, start: usize,
+        pos: usize) -> Result<(Vec<Box<Token>>, usize), ParseError> {
+    let mut items = Vec::new();
+    let mut substr_start = start;
 
-            let arg = parse_expr(code.split_at(substr_start).1, pos + substr_start);
-            if arg.is_err() {
-                return None;
-            }
-            args.push(Box::new(arg.clone().unwrap().token));
-            substr_start += arg.unwrap().new_start;
-        }
-    }
-    
-    let par = parse_word(")", code.split_at(substr_start).1);
-    if par.is_none() {
-        return None;
+    if let Some(end) = parse_word(close, code.split_at(substr_start).1) {
+        return Ok((items, substr_start + end.new_start));
     }
-    substr_start += par.unwrap().new_start;
 
-    Some(ParseResult {
-        new_start: substr_start,
-        token: Token::FunctionCall(fname_str, args)
-    })
+    loop {
+        let item = parse_expr(code.split_at(substr_start).1, pos + substr_start)?;
+        items.push(Box::new(item.token));
+        substr_start += item.new_start;
+
+        if let Some(comma) = parse_word(",", code.split_at(substr_start).1) {
+            substr_start += comma.new_start;
+        } else if let Some(end) = parse_word(close, code.split_at(substr_start).1) {
+            return Ok((items, substr_start + end.new_start));
+        } else {
+            let close_str = format!("'{}'", close);
+            return Err(unclosed(
+                open, code.split_at(substr_start).1, pos + substr_start,
+                &[ "','", close_str.as_str() ]
+            ));
+        }
+    }
 }
 
 /* Fundamental, underlying data types */
 
-// <int> ::= /-?[0-9][0-9_]*_/
-fn parse_integer(code: &str) -> Option<ParseResult> {
-    let mut int_str = String::new();
-    let mut i = 0;
+// <int> ::= /[0-9][0-9_]*_/ | /0x[0-9A-Fa-f_]+_/ | /0o[0-7_]+_/ | /0b[01_]+_/
+pub fn parse_integer(code: &str) -> Option<ParseResult> {
+    let bytes = code.as_bytes();
 
-    // Get 0-9+, but allow inner '_' for breaking up big numbers
-    while i < code.len() && (
-        code.chars().nth(i).unwrap().is_digit(10) || code.chars().nth(i).unwrap() == '_'
-    ) {
-        // No starting with '_'
-        if i == 0 && !code.chars().nth(i).unwrap().is_digit(10) {
-            return None;
-        }
+    // No starting with '_'
+    if bytes.is_empty() || !bytes[0].is_ascii_digit() {
+        return None;
+    }
+
+    // Other bases have a prefix saying which, then the same rules for digits
+    let (radix, mut i) = match code.get(..2) {
+        Some("0x") => (16, 2),
+        Some("0o") => (8, 2),
+        Some("0b") => (2, 2),
+        _ => (10, 0)
+    };
+    let start = i;
 
-        int_str.push(code.chars().nth(i).unwrap());
+    // Get the digits, but allow inner '_' for breaking up big numbers
+    while i < bytes.len() && ((bytes[i] as char).is_digit(radix) || bytes[i] == b'_') {
         i += 1;
     }
 
     // Finally, make sure it's a number and ends with _ and ship it
-    if int_str.len() < 2 || !int_str.ends_with('_') {
+    let int_str = code.split_at(i).0;
+    if i < start + 2 || bytes[start] == b'_' || !int_str.ends_with('_') {
         None
     } else {
         let skip_ws = parse_whitespace(code.split_at(i).1);
-        i += skip_ws.new_start;
         Some(ParseResult {
-            new_start: i,
-            token: Token::Integer(int_str.clone())
+            new_start: i + skip_ws.new_start,
+            token: Token::Integer(String::from(int_str))
         })
     }
 }
 
-// <float> ::= /\-?([0-9]*\.)?[0-9]+([Ee]\-?[0-9]+)?/
-fn parse_number(code: &str) -> Option<ParseResult> {
-    let mut float_str = String::new();
+// <float> ::= /([0-9]*\.)?[0-9]+([Ee]\-?[0-9]+)?/
+pub fn parse_number(code: &str) -> Option<ParseResult> {
+    let bytes = code.as_bytes();
     let mut i = 0;
     let mut found_pt = false;
+    let mut found_digit = false;
 
     // Get 0-9+ and 0-9+.0-9+
-    while i < code.len() && (
-        code.chars().nth(i).unwrap().is_digit(10)
-            || (code.chars().nth(i).unwrap() == '.' && !found_pt)
-    ) {
-        if code.chars().nth(i).unwrap() == '.' {
+    while i < bytes.len() && (bytes[i].is_ascii_digit() || (bytes[i] == b'.' && !found_pt)) {
+        if bytes[i] == b'.' {
             found_pt = true;
+        } else {
+            found_digit = true;
         }
-
-        float_str.push(code.chars().nth(i).unwrap());
         i += 1;
     }
+    if !found_digit {
+        return None;
+    }
 
     // Scientific notation
-    if i < code.len() && (
-        code.chars().nth(i).unwrap() == 'E' || code.chars().nth(i).unwrap() == 'e'
-        ) {
-        // Might fail, so don't adjust i and str unless successful
+    if i < bytes.len() && (bytes[i] == b'E' || bytes[i] == b'e') {
+        // Might fail, so don't adjust i unless successful
         let mut j = i + 1;
-        let mut e_str = String::from("E");
-        
+
         // Handle negative in size
-        if code.chars().nth(j).unwrap() == '-' {
-            e_str.push('-');
+        if j < bytes.len() && bytes[j] == b'-' {
             j += 1;
         }
 
         // Get the 0-9 part
-        let mut num_str = String::new();
-        while j < code.len() && code.chars().nth(j).unwrap().is_digit(10) {
-            num_str.push(code.chars().nth(j).unwrap());
+        let digits_start = j;
+        while j < bytes.len() && bytes[j].is_ascii_digit() {
             j += 1;
         }
 
-        if num_str.len() > 0 {
+        if j > digits_start {
             // Success!
             i = j;
-            float_str += e_str.as_str();
-            float_str += num_str.as_str();
         }
     }
 
-    if float_str.len() > 0 {
-        let skip_ws = parse_whitespace(code.split_at(i).1);
-        i += skip_ws.new_start;
-        Some(ParseResult {
-            new_start: i,
-            token: Token::Number(float_str.clone())
-        })
-    } else {
-        None
-    }
+    let float_str = code.split_at(i).0;
+    let skip_ws = parse_whitespace(code.split_at(i).1);
+    Some(ParseResult {
+        new_start: i + skip_ws.new_start,
+        token: Token::Number(String::from(float_str))
+    })
 }
 
 // <ident> ::= /[A-Za-z_]+[A-Za-z_0-9]*/
-fn parse_ident(code: &str) -> Option<ParseResult> {
-    let mut i = 0;
-    let mut ident_str = String::new();
-
-    // Make sure no num start
-    if code.len() > 0 && (
-        code.chars().nth(0).unwrap().is_alphabetic() || code.chars().nth(0).unwrap() == '_'
-    ) && code.chars().nth(0).unwrap() != 'j' {
-        // Then get everything
-        while i < code.len() && (
-            code.chars().nth(i).unwrap().is_ascii_alphanumeric()
-                || code.chars().nth(i).unwrap() == '_'
-        ) {
-            ident_str.push(code.chars().nth(i).unwrap());
-            i += 1;
-        }
+pub fn parse_ident(code: &str) -> Option<ParseResult> {
+    // Make sure no num start (and 'j' is reserved for the imaginary prefix)
+    match code.chars().next() {
+        Some(c) if (c.is_alphabetic() || c == '_') && c != 'j' => {},
+        _ => return None
     }
 
-    if ident_str.len() > 0 {
-        let skip_ws = parse_whitespace(code.split_at(i).1);
-        i += skip_ws.new_start;
-        Some(ParseResult {
-            new_start: i,
-            token: Token::Identifier(ident_str.clone())
-        })
-    } else {
-        None    
-    }
+    // Then get everything
+    let len = code.char_indices()
+        .find(|(i, c)| *i > 0 && !c.is_ascii_alphanumeric() && *c != '_')
+        .map(|(i, _)| i)
+        .unwrap_or(code.len());
+
+    let skip_ws = parse_whitespace(code.split_at(len).1);
+    Some(ParseResult {
+        new_start: len + skip_ws.new_start,
+        token: Token::Identifier(String::from(code.split_at(len).0))
+    })
 }
 
 /* True helper functions */
 
 // Get a specified string of characters
 fn parse_word(word: &str, code: &str) -> Option<ParseResult> {
-    if word.len() <= code.len() {
-        let mut i = 0;
-        while i < word.len() {
-            if word.chars().nth(i).unwrap() != code.chars().nth(i).unwrap() {
-                return None;
-            }
-            i += 1;
-        }
-        let skip_ws = parse_whitespace(code.split_at(i).1);
-        i += skip_ws.new_start;
+    if code.starts_with(word) {
+        let skip_ws = parse_whitespace(code.split_at(word.len()).1);
         Some(ParseResult {
-            new_start: i,
+            new_start: word.len() + skip_ws.new_start,
             token: Token::Word(String::from(word))
         })
     } else {
-        None        
+        None
     }
 }
 
+// Like parse_word, but it can't just be the start of a longer identifier, i.e. 'let' vs 'letter'
+fn parse_keyword(word: &str, code: &str) -> Option<ParseResult> {
+    match code.split_at(word.len().min(code.len())).1.chars().next() {
+        Some(c) if c.is_alphanumeric() || c == '_' => None,
+        _ => parse_word(word, code)
+    }
+}
+
+// Comments count as whitespace too
 fn parse_whitespace(code: &str) -> ParseResult {
-    let mut i = 0;
-    while i < code.len() && code.chars().nth(i).unwrap().is_whitespace() {
-        i += 1;
+    let mut len = 0;
+    loop {
+        len += code.split_at(len).1.char_indices()
+            .find(|(_, c)| !c.is_whitespace())
+            .map(|(i, _)| i)
+            .unwrap_or(code.len() - len);
+        if !code.split_at(len).1.starts_with('#') {
+            break;
+        }
+        len += code.split_at(len).1.find('\n').unwrap_or(code.len() - len);
     }
     ParseResult {
-        new_start: i,
+        new_start: len,
         token: Token::Whitespace
     }
 }
 
+// Like parse_word, but a missing word is an error. Gives back how much was consumed
+fn expect_word(word: &str, code: &str, pos: usize) -> Result<usize, ParseError> {
+    match parse_word(word, code) {
+        None => Err(unexpected(code, pos, &[ format!("'{}'", word).as_str() ])),
+        Some(res) => Ok(res.new_start)
+    }
+}
+
+fn expect_ident(code: &str, pos: usize, what: &str) -> Result<ParseResult, ParseError> {
+    parse_ident(code).ok_or_else(|| unexpected(code, pos, &[ what ]))
+}
+
+fn ident_str(token: Token) -> String {
+    if let Token::Identifier(name) = token {
+        name
+    } else {
+        String::new()
+    }
+}
+
+// Build an error pointing at whatever sits at the start of code (or the end if nothing does)
+fn unexpected(code: &str, pos: usize, expected: &[&str]) -> ParseError {
+    let found = next_lexeme(code);
+    if found.is_empty() {
+        ParseError::new(ParseErrorKind::UnexpectedEnd, (pos, pos), expected)
+    } else {
+        ParseError::new(
+            ParseErrorKind::UnexpectedToken(String::from(found)),
+            (pos, pos + found.len()),
+            expected
+        )
+    }
+}
+
+fn unclosed(open: char, code: &str, pos: usize, expected: &[&str]) -> ParseError {
+    let mut err = unexpected(code, pos, expected);
+    err.kind = ParseErrorKind::UnclosedDelimiter(open);
+    err
+}
+
+// The next "word" - a run of identifier/number characters or else a single other character
+fn next_lexeme(code: &str) -> &str {
+    let len = code.char_indices()
+        .find(|(_, c)| !c.is_alphanumeric() && *c != '_' && *c != '.')
+        .map(|(i, _)| i)
+        .unwrap_or(code.len());
+    if len > 0 {
+        code.split_at(len).0
+    } else {
+        code.split_at(code.chars().next().map(char::len_utf8).unwrap_or(0)).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_stmt, parse_integer, is_incomplete, strip_comment, Token
+    };
+    use crate::int::Int;
+
+    // Put brackets around every operator so the shape of the tree shows, e.g. 1 + 2 * 3 -> (1 + (2 * 3))
+    fn grouped(code: &str) -> String {
+        fn show(token: &Token) -> String {
+            match token {
+                Token::Statement(inner) | Token::Term(inner)
+                    | Token::Expression(inner, None, None)
+                    | Token::UnaryExpression(inner, None)
+                    | Token::ExponentialExpression(inner, None) => show(inner),
+                Token::Expression(cond, Some(t), Some(e)) => format!(
+                    "({} ? {} : {})", show(cond), show(t), show(e)
+                ), Token::UnaryExpression(inner, Some(op)) => format!("({}{})", op, show(inner)),
+                Token::ExponentialExpression(base, Some(power)) => format!(
+                    "({} ^ {})", show(base), show(power)
+                ), Token::ProductExpression(first, rest)
+                    | Token::SumExpression(first, rest)
+                    | Token::ShiftExpression(first, rest)
+                    | Token::RelationalExpression(first, rest)
+                    | Token::AndExpression(first, rest)
+                    | Token::XorExpression(first, rest)
+                    | Token::OrExpression(first, rest) => rest.iter().fold(
+                        show(first), |acc, (op, operand)| format!("({} {} {})", acc, op, show(operand))
+                    ),
+                token => token.to_string()
+            }
+        }
+        show(&parse_stmt(code).unwrap())
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(grouped("1 + 2 * 3"), "(1 + (2 * 3))");
+        assert_eq!(grouped("1 * 2 + 3"), "((1 * 2) + 3)");
+        assert_eq!(grouped("1 << 2 + 3"), "(1 << (2 + 3))");
+        assert_eq!(grouped("1 < 2 << 3"), "(1 < (2 << 3))");
+        assert_eq!(grouped("x & 1 = 1"), "(x & (1 = 1))");
+        assert_eq!(grouped("1 xor 2 & 3"), "(1 xor (2 & 3))");
+        assert_eq!(grouped("1 | 2 xor 3"), "(1 | (2 xor 3))");
+        assert_eq!(
+            grouped("a | b xor c & d = e << f + g * h"),
+            "(a | (b xor (c & (d = (e << (f + (g * h)))))))"
+        );
+        assert_eq!(grouped("1 | 2 ? 3 : 4 + 5"), "((1 | 2) ? 3 : (4 + 5))");
+    }
+
+    #[test]
+    fn associativity() {
+        assert_eq!(grouped("8 - 2 - 1"), "((8 - 2) - 1)");
+        assert_eq!(grouped("8 / 2 / 2"), "((8 / 2) / 2)");
+        assert_eq!(grouped("1 << 2 >> 3"), "((1 << 2) >> 3)");
+        assert_eq!(grouped("1 xor 2 xor 3"), "((1 xor 2) xor 3)");
+        assert_eq!(grouped("2 ^ 3 ^ 2"), "(2 ^ (3 ^ 2))");
+    }
+
+    #[test]
+    fn unary() {
+        assert_eq!(grouped("-2^2"), "(-(2 ^ 2))");
+        assert_eq!(grouped("2^-1"), "(2 ^ (-1))");
+        assert_eq!(grouped("--x * y"), "((-(-x)) * y)");
+        assert_eq!(grouped("~1 & 2"), "((~1) & 2)");
+        assert_eq!(grouped("xor_x"), "xor_x");
+    }
+
+    #[test]
+    fn int_literals() {
+        let lex = |code: &str| parse_integer(code).map(|res| (res.token.to_string(), res.new_start));
+        assert_eq!(lex("0x1F_ + 1"), Some((String::from("0x1F_"), 6)));
+        assert_eq!(lex("0xff_ff_"), Some((String::from("0xff_ff_"), 8)));
+        assert_eq!(lex("0o17_"), Some((String::from("0o17_"), 5)));
+        assert_eq!(lex("0b1010_"), Some((String::from("0b1010_"), 7)));
+        assert_eq!(lex("1_000_"), Some((String::from("1_000_"), 6)));
+        for bad in [ "_1_", "12", "0x_", "0x_1_", "0b2_", "0o8_", "x1_" ] {
+            assert!(lex(bad).is_none(), "{} shouldn't be an int", bad);
+        }
+
+        // The same way eval reads them
+        let value = |code: &str| parse_integer(code).unwrap().token.to_string().replace('_', "").parse::<Int>();
+        assert_eq!(value("0x1F_"), Ok(Int::from(31)));
+        assert_eq!(value("0o17_"), Ok(Int::from(15)));
+        assert_eq!(value("0b1010_"), Ok(Int::from(10)));
+        assert_eq!(value("0xff_ff_"), Ok(Int::from(65535)));
+    }
+
+    #[test]
+    fn incomplete() {
+        for code in [
+            "(1 + 2", "[1, 2", "f(1,", "1 +", "2 * # more to come", "\\f(x) ->", "\\f(x", "let a :=",
+            "x ? 1", "x ? 1 :", "1 +\n# just a comment"
+        ] {
+            assert!(is_incomplete(code), "{:?} should be incomplete", code);
+        }
+        for code in [ "", "# nothing", "1 + 2", "1 + 2 # done", "(1 + 2)\n", "1 + )", "\\f(x) -> x", "1 2" ] {
+            assert!(!is_incomplete(code), "{:?} shouldn't be incomplete", code);
+        }
+    }
+
+    // The REPL squashes continued statements onto one line for history, which has to mean the same
+    #[test]
+    fn joined_lines() {
+        let join = |code: &str| code.lines()
+            .map(|line| strip_comment(line).trim()).filter(|line| !line.is_empty())
+            .collect::<Vec<&str>>().join(" ");
+        for code in [ "1 + # first part\n2", "\\f(x) -> # doubles\n# still going\n2 * x", "[1, # one\n2]" ] {
+            let joined = join(code);
+            assert!(!is_incomplete(&joined), "{:?} was joined as {:?}", code, joined);
+            assert_eq!(grouped(&joined), grouped(code));
+        }
+    }
+
+    // Showing a tree and parsing it again gives the same tree
+    #[test]
+    fn display_round_trip() {
+        for code in [
+            "1 + 2 * 3", "(1 + 2) * 3", "8 - (2 - 1)", "(2 ^ 3) ^ 2", "-2^2", "(-2)^2", "j2 * ~x",
+            "x & 1 = 1", "(x & 1) = 1", "1 xor 2 | 3 << 4", "x > 0 ? [ 1, 2.5 ] : f(x, 0x1f_)",
+            "\\f(x, y) -> x^y + 1", "let a := (1 + 2) / 3"
+        ] {
+            let token = parse_stmt(code).unwrap();
+            let shown = token.to_string();
+            assert_eq!(grouped(&shown), grouped(code), "{} was shown as {}", code, shown);
+            assert_eq!(parse_stmt(&shown).unwrap().to_string(), shown);
+        }
+    }
+}
@@ -5,7 +5,10 @@
  * - Under the hood, it uses this code to do so
  */
 
-use std::path::Path;
+use std::{
+    path::Path,
+    fs::read_dir
+};
 use dirs::config_dir;
 use dlopen_derive::WrapperApi;
 use dlopen::{
@@ -14,17 +17,69 @@ use dlopen::{
         Container, WrapperApi
     }
 };
-use crate::var::Var;
+use crate::{
+    var::{
+        Var, ratio_to_f64
+    }, error::CalcError,
+    int::Int,
+    complex::{
+        FComplex, IComplex
+    }
+};
+
+// What plugins actually see. It's kept separate from Var so the interpreter's number types can
+// change without breaking every library built against the old layout (see ex_plug/)
+#[repr(C)]
+#[derive(Clone, Debug)]
+pub enum PluginVar {
+    Int { re: i64, im: i64 },
+    Float { re: f64, im: f64 },
+    List(Vec<PluginVar>)
+}
+
+// Plugins only get 64-bit ints, so anything bigger can't be passed along
+impl TryFrom<&Var> for PluginVar {
+    type Error = CalcError;
+    fn try_from(var: &Var) -> Result<Self, Self::Error> {
+        match var {
+            Var::Int(int) => match (int.re.to_i64(), int.im.to_i64()) {
+                (Some(re), Some(im)) => Ok(PluginVar::Int { re, im }),
+                _ => Err(CalcError::Plugin(format!(
+                    "{} is too large to pass to a plugin", int.to_string()
+                )))
+            }, Var::Rational(ratio) => Ok(PluginVar::Float { re: ratio_to_f64(ratio), im: 0.0 }),
+            Var::Decimal(num) => Ok(PluginVar::Float { re: num.to_f64(), im: 0.0 }),
+            Var::Float(num) => {
+                let (re, im) = num.to_cardinal();
+                Ok(PluginVar::Float { re, im })
+            }, Var::List(ls) => Ok(PluginVar::List(
+                ls.iter().map(PluginVar::try_from).collect::<Result<Vec<PluginVar>, CalcError>>()?
+            ))
+        }
+    }
+}
+
+impl From<PluginVar> for Var {
+    fn from(var: PluginVar) -> Self {
+        match var {
+            PluginVar::Int { re, im } => Var::Int(
+                IComplex::new_cardinal(Int::from(re), Int::from(im))
+            ),
+            PluginVar::Float { re, im } => Var::Float(FComplex::new_cardinal(re, im)),
+            PluginVar::List(ls) => Var::List(ls.into_iter().map(Var::from).collect())
+        }
+    }
+}
 
 #[derive(WrapperApi)]
 pub struct Plugin {
-    execute: extern fn(vars: &Vec<Var>) -> Result<Var, String>
+    execute: extern "Rust" fn(vars: &Vec<PluginVar>) -> Result<PluginVar, String>
 }
 
-pub fn call_ext_fn(lib_name: &str, vars: &Vec<Var>) -> Result<Var, String> {
+pub fn call_ext_fn(lib_name: &str, vars: &[Var]) -> Result<Var, CalcError> {
     let conf = config_dir();
     if conf.is_none() {
-        Err(String::from("Config directory does not exist."))
+        Err(CalcError::Plugin(String::from("Config directory does not exist.")))
     } else {
         let mut plugin_dir = conf.unwrap();
         plugin_dir.push("calc");
@@ -39,17 +94,38 @@ pub fn call_ext_fn(lib_name: &str, vars: &Vec<Var>) -> Result<Var, String> {
                 };
                 match plugin_res {
                     Ok(plugin) => {
-                        plugin.execute(vars)
-                    }, Err(err) => Err(
+                        let plugin_vars = vars.iter()
+                            .map(PluginVar::try_from)
+                            .collect::<Result<Vec<PluginVar>, CalcError>>()?;
+                        plugin.execute(&plugin_vars).map(Var::from).map_err(CalcError::Plugin)
+                    }, Err(err) => Err(CalcError::Plugin(
                         format!("Failed to run library execute function - {}.", err)
-                    )
+                    ))
                 }
             } else {
-                Err(String::from("Specified library does not exist."))
+                Err(CalcError::Plugin(String::from("Specified library does not exist.")))
             }
         } else {
-            Err(String::from("Plugin directory does not exist."))
+            Err(CalcError::Plugin(String::from("Plugin directory does not exist.")))
         }
     }
 } 
 
+
+// Names that can be passed to 'call', i.e. every lib<name>.so in the plugin directory
+pub fn plugin_names() -> Vec<String> {
+    let mut plugin_dir = match config_dir() {
+        None => return Vec::new(),
+        Some(conf) => conf
+    };
+    plugin_dir.push("calc");
+    plugin_dir.push("plugins");
+
+    let mut names = read_dir(plugin_dir).map(|entries| entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|file| Some(String::from(file.strip_prefix("lib")?.strip_suffix(".so")?)))
+        .collect::<Vec<String>>()
+    ).unwrap_or_default();
+    names.sort();
+    names
+}
@@ -0,0 +1,187 @@
+/*
+ * Author: Dylan Turner
+ * Description:
+ * - Per-session options that change how things get calculated
+ * - Changed with ':set <name> <value>' in the repl or the init file, or '--set name=value'
+ */
+
+use crate::{
+    error::CalcError,
+    format::FLOAT_DIGITS,
+    word::{
+        Word, MAX_BITS
+    }
+};
+
+// Which kind of float literals like 0.1 turn into
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FloatMode {
+    Binary, // Hardware f64s. Fast, but 0.1 + 0.2 =/= 0.3
+    Decimal // Base 10 with 'precision' significant digits
+}
+
+// How many significant digits floats are shown with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Digits {
+    Auto, // Binary floats get FLOAT_DIGITS so 0.1 + 0.2 shows as 0.3, and decimal floats get all of theirs
+    All, // Every digit, so binary floats come out as the shortest text that reads back as the same f64
+    Count(u32) // Round everything to this many
+}
+
+// How numbers get written out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Notation {
+    Auto, // Plain digits, except decimal floats that are too long to write out that way
+    Fixed, // Always plain digits, e.g. 1234.5
+    Scientific, // One digit before the point, e.g. 1.2345e3
+    Engineering // Exponents are multiples of 3, e.g. 1.2345e3 or 12.345e-6
+}
+
+// How numbers with an imaginary part get written out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ComplexStyle {
+    Rect, // 1 + j2, the same way they're typed
+    Polar, // len∠angle with the angle in radians
+    PolarDeg // len∠angle° with the angle in degrees
+}
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub float_mode: FloatMode,
+    pub precision: u32,
+    pub history_size: usize, // Lines of repl history to keep
+    pub result_size: usize, // Results to keep around as _1, _2, etc
+    pub digits: Digits,
+    pub notation: Notation,
+    pub grouping: bool, // Commas between every 3 digits before the point
+    pub complex_style: ComplexStyle,
+    pub base: u32, // What base ints are shown in, from 2 to 36
+    pub word: Option<Word> // Fixed width that ints wrap around to in programmer mode, or None for no limit
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self {
+            float_mode: FloatMode::Binary,
+            precision: 30,
+            history_size: 1000,
+            result_size: 100,
+            digits: Digits::Auto,
+            notation: Notation::Auto,
+            grouping: false,
+            complex_style: ComplexStyle::Rect,
+            base: 10,
+            word: None
+        }
+    }
+
+    // Change a setting by name, giving back a message saying what happened
+    pub fn set(&mut self, name: &str, value: &str) -> Result<String, CalcError> {
+        match name {
+            "float" => {
+                self.float_mode = match value {
+                    "binary" => FloatMode::Binary,
+                    "decimal" => FloatMode::Decimal,
+                    _ => return Err(CalcError::InvalidCommand(format!(
+                        "'float' must be 'binary' or 'decimal', not '{}'", value
+                    )))
+                };
+                Ok(format!("Floats are now {}", value))
+            }, "precision" => {
+                self.precision = match value.parse::<u32>() {
+                    Ok(digits) if (1..=10000).contains(&digits) => digits,
+                    _ => return Err(CalcError::InvalidCommand(format!(
+                        "'precision' must be a number of digits from 1 to 10000, not '{}'", value
+                    )))
+                };
+                Ok(format!("Decimal floats now keep {} significant digits", self.precision))
+            }, "history" => {
+                self.history_size = value.parse::<usize>().map_err(|_| CalcError::InvalidCommand(format!(
+                    "'history' must be a number of lines, not '{}'", value
+                )))?;
+                Ok(format!("Keeping the last {} lines of history", self.history_size))
+            }, "results" => {
+                self.result_size = value.parse::<usize>().map_err(|_| CalcError::InvalidCommand(format!(
+                    "'results' must be a number of results, not '{}'", value
+                )))?;
+                Ok(format!("Keeping the last {} results", self.result_size))
+            }, "digits" => {
+                self.digits = match value {
+                    "auto" => Digits::Auto,
+                    "all" => Digits::All,
+                    _ => match value.parse::<u32>() {
+                        Ok(digits) if (1..=10000).contains(&digits) => Digits::Count(digits),
+                        _ => return Err(CalcError::InvalidCommand(format!(
+                            "'digits' must be 'auto', 'all' or a number of digits from 1 to 10000, not '{}'", value
+                        )))
+                    }
+                };
+                Ok(match self.digits {
+                    Digits::Auto => format!(
+                        "Showing {} significant digits of binary floats and every digit of decimal ones",
+                        FLOAT_DIGITS
+                    ), Digits::All => String::from("Showing every digit of floats"),
+                    Digits::Count(digits) => format!("Showing {} significant digits of floats", digits)
+                })
+            }, "notation" => {
+                self.notation = match value {
+                    "auto" => Notation::Auto,
+                    "fixed" => Notation::Fixed,
+                    "sci" => Notation::Scientific,
+                    "eng" => Notation::Engineering,
+                    _ => return Err(CalcError::InvalidCommand(format!(
+                        "'notation' must be 'auto', 'fixed', 'sci' or 'eng', not '{}'", value
+                    )))
+                };
+                Ok(format!("Numbers are now shown in {} notation", value))
+            }, "grouping" => {
+                self.grouping = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(CalcError::InvalidCommand(format!(
+                        "'grouping' must be 'on' or 'off', not '{}'", value
+                    )))
+                };
+                Ok(format!("Digit grouping is now {}", value))
+            }, "complex" => {
+                self.complex_style = match value {
+                    "rect" => ComplexStyle::Rect,
+                    "polar" => ComplexStyle::Polar,
+                    "deg" => ComplexStyle::PolarDeg,
+                    _ => return Err(CalcError::InvalidCommand(format!(
+                        "'complex' must be 'rect', 'polar' or 'deg', not '{}'", value
+                    )))
+                };
+                Ok(format!("Complex numbers are now shown as {}", value))
+            }, "base" => {
+                self.base = match value {
+                    "hex" => 16,
+                    "dec" => 10,
+                    "oct" => 8,
+                    "bin" => 2,
+                    _ => match value.parse::<u32>() {
+                        Ok(base) if (2..=36).contains(&base) => base,
+                        _ => return Err(CalcError::InvalidCommand(format!(
+                            "'base' must be hex, dec, oct, bin or a number from 2 to 36, not '{}'", value
+                        )))
+                    }
+                };
+                Ok(format!("Ints are now shown in base {}", self.base))
+            }, "word" => {
+                self.word = match value {
+                    "off" => None,
+                    _ => match Word::parse(value) {
+                        Some(word) => Some(word),
+                        None => return Err(CalcError::InvalidCommand(format!(
+                            "'word' must be 'off' or a size like u8 or i32, up to {} bits, not '{}'", MAX_BITS, value
+                        )))
+                    }
+                };
+                Ok(match self.word {
+                    None => String::from("Ints no longer have a fixed width"),
+                    Some(word) => format!("Ints are now {}s, wrapping around like the hardware does", word)
+                })
+            }, _ => Err(CalcError::InvalidCommand(format!("No such setting '{}'", name)))
+        }
+    }
+}
@@ -3,247 +3,595 @@
  * Description:
  * - A variable type for use in the interpreter.
  * - Implements several functions between vars
- * - Real/Complex and Int/Float/List
+ * - Real/Complex and Int/Rational/Float/Decimal/List
  */
 
-use std::ops::{
-    Add, Sub, Mul, Div, BitXor
+use std::{
+    cmp::Ordering,
+    ops::{
+        Add, Sub, Mul, Div, BitXor
+    }
+};
+use num_rational::BigRational;
+use num_traits::{
+    ToPrimitive, Zero
 };
-use crate::complex::{
-    FComplex, IComplex
+use crate::{
+    error::CalcError,
+    int::{
+        Int, MAX_RESULT_BITS
+    },
+    decimal::Decimal,
+    settings::Settings,
+    complex::{
+        FComplex, IComplex
+    }, format::{
+        format_icomplex, format_ratio, format_fcomplex, format_decimal
+    }
 };
 
-// Can be list, complex float, or complex int
+// Can be list, complex float, complex int, a real fraction, or a real base 10 float
 #[derive(Clone, Debug)]
-pub struct Var {
-    pub ls_data: Option<Vec<Var>>,
-    pub num_data: Option<FComplex>,
-    pub int_data: Option<IComplex>
+pub enum Var {
+    Int(IComplex),
+    Rational(BigRational),
+    Float(FComplex),
+    Decimal(Decimal),
+    List(Vec<Var>)
 }
 
-// Mainly the base functions 
+// Mainly the base functions
 impl Var {
-    pub fn to_string(&self) -> String {
-        if self.num_data.is_some() {
-            self.num_data.unwrap().to_string()
-        } else if self.int_data.is_some() {
-            self.int_data.unwrap().to_string()
-        } else if self.ls_data.is_some() {
-            let mut repr = String::new();
-            repr.push_str("[ ");
-            for var in self.ls_data.clone().unwrap() {
-                repr.push_str(var.to_string().as_str());
-                repr.push(' ');
+    // Written out the way the display settings say
+    pub fn to_string(&self, settings: &Settings) -> String {
+        match self {
+            Var::Int(int) => format_icomplex(int, settings),
+            Var::Rational(ratio) => format_ratio(ratio, settings),
+            Var::Float(num) => format_fcomplex(*num, settings),
+            Var::Decimal(num) => format_decimal(num, settings),
+            Var::List(ls) => {
+                let mut repr = String::new();
+                repr.push_str("[ ");
+                for var in ls {
+                    repr.push_str(var.to_string(settings).as_str());
+                    repr.push(' ');
+                }
+                repr.push(']');
+                repr
             }
-            repr.push(']');
-            repr
-        } else {
-            String::from("IMPOSSIBLE DATA ACHIEVED")
         }
     }
 
-    pub fn impossible() -> Self {
-        Self {
-            ls_data: None,
-            num_data: None,
-            int_data: None
+    // Only zero counts as false in conditions, and a list is never zero
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Var::Int(int) => int.re.is_zero() && int.im.is_zero(),
+            Var::Rational(ratio) => ratio.is_zero(),
+            Var::Float(num) => num.re == 0.0 && num.im == 0.0,
+            Var::Decimal(num) => num.is_zero(),
+            Var::List(_) => false
         }
     }
 
-    pub fn to_lat(&self) -> Self {
-        let mut new_self = self.clone();
+    // Fractions that come out whole go back to being plain ints
+    pub fn from_ratio(ratio: BigRational) -> Self {
+        if ratio.is_integer() {
+            Var::Int(IComplex::new_cardinal(Int::from_big(ratio.to_integer()), Int::from(0)))
+        } else {
+            Var::Rational(ratio)
+        }
+    }
 
-        if new_self.ls_data.is_some() {
-            let mut new_ls = Vec::new();
-            let ls = new_self.ls_data.unwrap();
-            for var in ls {
-                new_ls.push(var.to_lat());
-            }
-            new_self.ls_data = Some(new_ls);
-        } else if new_self.num_data.is_some() {
-            let flipped = new_self.num_data.unwrap();
-            let (real, _) = flipped.to_cardinal();
-            new_self.num_data = Some(FComplex::new_cardinal(0.0, real));
-        } else if new_self.int_data.is_some() {
-            let flipped = new_self.int_data.unwrap();
-            let (real, _) = flipped.to_cardinal();
-            new_self.int_data = Some(IComplex::new_cardinal(0, real));
+    // Multiply by j
+    pub fn to_lat(&self) -> Self {
+        match self {
+            Var::Int(int) => Var::Int(IComplex::new_cardinal(-int.im.clone(), int.re.clone())),
+            Var::Rational(ratio) => Var::Float(FComplex::new_cardinal(0.0, ratio_to_f64(ratio))),
+            Var::Float(num) => Var::Float(FComplex::new_cardinal(-num.im, num.re)),
+            Var::Decimal(num) => Var::Float(FComplex::new_cardinal(0.0, num.to_f64())),
+            Var::List(ls) => Var::List(ls.iter().map(|var| var.to_lat()).collect())
         }
-        
-        new_self
     }
 
     pub fn to_neg(&self) -> Self {
-        let mut new_self = self.clone();
-
-        if new_self.ls_data.is_some() {
-            let mut new_ls = Vec::new();
-            let ls = new_self.ls_data.unwrap();
-            for var in ls {
-                new_ls.push(var.to_neg());
-            }
-            new_self.ls_data = Some(new_ls);
-        } else if new_self.num_data.is_some() {
-            let data = new_self.num_data.unwrap();
-            new_self.num_data = Some(FComplex::new_polar(-data.len, data.angle));
-        } else if new_self.int_data.is_some() {
-            let data = new_self.int_data.unwrap();
-            new_self.int_data = Some(IComplex::new_polar(-data.len, data.angle_deg));
+        match self {
+            Var::Int(int) => Var::Int(IComplex::new_cardinal(-int.re.clone(), -int.im.clone())),
+            Var::Rational(ratio) => Var::Rational(-ratio.clone()),
+            Var::Float(num) => Var::Float(FComplex::new_cardinal(-num.re, -num.im)),
+            Var::Decimal(num) => Var::Decimal(-num.clone()),
+            Var::List(ls) => Var::List(ls.iter().map(|var| var.to_neg()).collect())
         }
-        
-        new_self
     }
 
     pub fn to_float(&self) -> Self {
-        let mut new_self = self.clone();
+        match self {
+            Var::Int(int) => Var::Float(int.to_fcomplex()),
+            Var::Rational(ratio) => Var::Float(FComplex::new_cardinal(ratio_to_f64(ratio), 0.0)),
+            Var::Float(num) => Var::Float(*num),
+            Var::Decimal(num) => Var::Float(FComplex::new_cardinal(num.to_f64(), 0.0)),
+            Var::List(ls) => Var::List(ls.iter().map(|var| var.to_float()).collect())
+        }
+    }
 
-        if new_self.ls_data.is_some() {
-            let mut new_ls = Vec::new();
-            let ls = new_self.ls_data.unwrap();
-            for var in ls {
-                new_ls.push(var.to_float());
-            }
-            new_self.ls_data = Some(new_ls);
-        } else if new_self.int_data.is_some() {
-            new_self.num_data = Some(new_self.int_data.unwrap().to_fcomplex());
-        }
-        
-        new_self
-    }
-
-    pub fn do_cmp(self, other: Self, op: &str) -> Self {
-        match op {
-            "=" => self.do_op(
-                other,
-                |a, b| if a == b { FComplex::new_polar(-1.0, 0.0) } else { FComplex::zero() },
-                |a, b| if a == b { IComplex::new_polar(-1, 0) } else { IComplex::zero() }
-            ), "=/=" => self.do_op(
-                other,
-                |a, b| if a != b { FComplex::new_polar(-1.0, 0.0) } else { FComplex::zero() },
-                |a, b| if a != b { IComplex::new_polar(-1, 0) } else { IComplex::zero() }
-            ), ">" => self.do_op(
-                other,
-                |a, b| if a > b { FComplex::new_polar(-1.0, 0.0) } else { FComplex::zero() },
-                |a, b| if a > b { IComplex::new_polar(-1, 0) } else { IComplex::zero() }
-            ), "<" => self.do_op(
-                other,
-                |a, b| if a < b { FComplex::new_polar(-1.0, 0.0) } else { FComplex::zero() },
-                |a, b| if a < b { IComplex::new_polar(-1, 0) } else { IComplex::zero() }
-            ), ">=" => self.do_op(
-                other,
-                |a, b| if a >= b { FComplex::new_polar(-1.0, 0.0) } else { FComplex::zero() },
-                |a, b| if a >= b { IComplex::new_polar(-1, 0) } else { IComplex::zero() }
-            ), "<=" => self.do_op(
-                other,
-                |a, b| if a <= b { FComplex::new_polar(-1.0, 0.0) } else { FComplex::zero() },
-                |a, b| if a <= b { IComplex::new_polar(-1, 0) } else { IComplex::zero() }
-            ), _ => Self::impossible()
-        }
-    }
-
-    // Basically dec_op/int_op are +, -, etc, but this way I can reuse code
-    pub fn do_op<
-        DF: FnOnce(FComplex, FComplex) -> FComplex + Copy,
-        IF: FnOnce(IComplex, IComplex) -> IComplex + Copy
-    >(self, other: Self, dec_op: DF, int_op: IF) -> Self {
-        // Check for lists
-        if self.ls_data.is_some() && other.ls_data.is_some() {
-            // Both are lists, so do a matrix multiplication
-            let mut new_ls = Vec::new();
-            for var in self.ls_data.clone().unwrap() {
-                let mut folded = var.clone();
-                for other_var in other.ls_data.clone().unwrap() {
-                    folded = folded.do_op(other_var.clone(), dec_op, int_op);
-                }
-                new_ls.push(folded);
-            }
-            Var {
-                ls_data: Some(new_ls),
-                num_data: None,
-                int_data: None
-            }
-        } else if self.ls_data.is_some() {
-            // One is list, so do the operation to with other to every item
-            let mut cur = self.ls_data.clone().unwrap();
-            for var in cur.iter_mut() {
-                *var = var.clone().do_op(other.clone(), dec_op, int_op);
-            }
-            Var {
-                ls_data: Some(cur),
-                num_data: None,
-                int_data: None
-            }
-        } else if other.ls_data.is_some() {
-            // One is list, so do the operation to with other to every item
-            let mut cur = other.ls_data.clone().unwrap();
-            for var in cur.iter_mut() {
-                *var = var.clone().do_op(self.clone(), dec_op, int_op);
-            }
-            Var {
-                ls_data: Some(cur),
-                num_data: None,
-                int_data: None
+    // Like to_float, but into base 10. Complex values have no decimal form, so they stay as they are
+    pub fn to_decimal(&self, prec: u32) -> Self {
+        match self {
+            Var::List(ls) => Var::List(ls.iter().map(|var| var.to_decimal(prec)).collect()),
+            var => match var.exact_decimal(prec) {
+                Some(num) => Var::Decimal(num),
+                None => var.clone()
             }
+        }
+    }
+
+    // Real ints, fractions and other decimals can be carried over into a decimal exactly
+    fn exact_decimal(&self, prec: u32) -> Option<Decimal> {
+        match self {
+            Var::Int(int) if int.is_real() => Some(Decimal::from_int(&int.re.to_big(), prec)),
+            Var::Rational(ratio) => Some(Decimal::from_ratio(ratio, prec)),
+            Var::Decimal(num) => Some(num.with_prec(prec)),
+            _ => None
+        }
+    }
+
+    // Real ints and fractions can be done exactly as fractions
+    fn to_ratio(&self) -> Option<BigRational> {
+        match self {
+            Var::Int(int) if int.is_real() => Some(BigRational::from_integer(int.re.to_big())),
+            Var::Rational(ratio) => Some(ratio.clone()),
+            _ => None
+        }
+    }
+
+    // Comparisons give -1 for true and 0 for false
+    pub fn do_cmp(self, other: Self, op: &str) -> Result<Self, CalcError> {
+        let truth = |holds: bool| if holds {
+            Var::Int(IComplex::from_real(-1))
         } else {
-            // Check if using floats as it overrides intedness
-            if self.num_data.is_some() || self.num_data.is_some()
-                    || other.num_data.is_some() || other.num_data.is_some() {
-                let f_self = self.to_float();
-                let f_other = other.to_float();
-
-                let f_res = dec_op(f_self.num_data.unwrap(), f_other.num_data.unwrap());
-
-                Var {
-                    ls_data: None,
-                    num_data: Some(f_res),
-                    int_data: None
-                }
+            Var::Int(IComplex::zero())
+        };
+        self.do_op(
+            other,
+            |a, b| Ok(if cmp_holds(a, b, op)? {
+                FComplex::new_cardinal(-1.0, 0.0)
             } else {
-                // All ints
-                let res = int_op(self.int_data.unwrap(), other.int_data.unwrap());
-                Var {
-                    ls_data: None,
-                    num_data: None,
-                    int_data: Some(res)
-                }
+                FComplex::zero()
+            }), |a, b| Ok(truth(cmp_holds(a, b, op)?)),
+            |a, b| Ok(truth(cmp_holds(a, b, op)?)),
+            |a, b| Ok(truth(cmp_holds(a, b, op)?))
+        )
+    }
+
+    // Bitwise operators, '&', '|', 'xor', '<<' and '>>'. Only real ints have bits to work on
+    pub fn do_bitwise(self, other: Self, op: &str) -> Result<Self, CalcError> {
+        let not_int = || CalcError::TypeMismatch(format!("'{}' only works on ints", op));
+        self.do_op(
+            other,
+            |_, _| Err(not_int()), |a, b| {
+                let (a, b) = match (a.is_real(), b.is_real()) {
+                    (true, true) => (a.re, b.re),
+                    _ => return Err(CalcError::TypeMismatch(format!("'{}' only works on real ints", op)))
+                };
+                Ok(Var::Int(IComplex::new_cardinal(match op {
+                    "&" => a & b,
+                    "|" => a | b,
+                    "xor" => a ^ b,
+                    "<<" => {
+                        // Same limit as powers, since this is just as easy to blow up
+                        let n = shift_amount(&b)?;
+                        if !a.is_zero() && a.bits() + n as u64 > MAX_RESULT_BITS {
+                            return Err(CalcError::Domain(format!(
+                                "Shift of {} is too large, the result would be over {} bits", n, MAX_RESULT_BITS
+                            )));
+                        }
+                        a.shl(n)
+                    },
+                    // Shifting right far enough leaves nothing but the sign, however far it goes
+                    ">>" => a.shr(shift_amount(&b).or_else(|err| {
+                        if b.is_negative() { Err(err) } else { Ok(u32::MAX) }
+                    })?),
+                    _ => unreachable!("Unknown bitwise operator '{}'", op)
+                }, Int::from(0))))
+            }, |_, _| Err(not_int()), |_, _| Err(not_int())
+        )
+    }
+
+    // ~x, which is -x - 1 for ints
+    pub fn to_not(&self) -> Result<Self, CalcError> {
+        match self {
+            Var::Int(int) if int.is_real() => Ok(Var::Int(
+                IComplex::new_cardinal(!int.re.clone(), Int::from(0))
+            )), Var::Int(_) => Err(CalcError::TypeMismatch(String::from("'~' only works on real ints"))),
+            Var::List(ls) => Ok(Var::List(
+                ls.iter().map(|var| var.to_not()).collect::<Result<Vec<Var>, CalcError>>()?
+            )),
+            _ => Err(CalcError::TypeMismatch(String::from("'~' only works on ints")))
+        }
+    }
+
+    // Basically float_op/int_op/rat_op/dec_op are +, -, etc, but this way I can reuse code
+    // int_op, rat_op and dec_op give back a whole Var since the result may need to be a different
+    // kind, e.g. 1_/2_ is a fraction and 1_/2_ + 1_/2_ is an int again
+    pub fn do_op<
+        FF: FnOnce(FComplex, FComplex) -> Result<FComplex, CalcError> + Copy,
+        IF: FnOnce(IComplex, IComplex) -> Result<Var, CalcError> + Copy,
+        RF: FnOnce(BigRational, BigRational) -> Result<Var, CalcError> + Copy,
+        DF: FnOnce(Decimal, Decimal) -> Result<Var, CalcError> + Copy
+    >(
+            self, other: Self,
+            float_op: FF, int_op: IF, rat_op: RF, dec_op: DF) -> Result<Self, CalcError> {
+        self.zip_lists(other, move |a, b| match (a, b) {
+            (Var::Int(a), Var::Int(b)) => int_op(a, b),
+            (Var::Float(a), Var::Float(b)) => Ok(Var::Float(float_op(a, b)?)),
+            (Var::Decimal(a), b) => match b.exact_decimal(a.prec) {
+                // Exact reals join the decimal, but complex values and f64s win
+                Some(b) => dec_op(a, b),
+                None => Ok(Var::Float(float_op(FComplex::new_cardinal(a.to_f64(), 0.0), b.to_fcomplex())?))
+            }, (a, Var::Decimal(b)) => match a.exact_decimal(b.prec) {
+                Some(a) => dec_op(a, b),
+                None => Ok(Var::Float(float_op(a.to_fcomplex(), FComplex::new_cardinal(b.to_f64(), 0.0))?))
+            },
+            (a, b) => match (a.to_ratio(), b.to_ratio()) {
+                // At least one fraction, and the other can be one too
+                (Some(a), Some(b)) => rat_op(a, b),
+
+                // Floats override everything else
+                _ => Ok(Var::Float(float_op(a.to_fcomplex(), b.to_fcomplex())?))
             }
+        })
+    }
+
+    // Deal with lists for an op, so op itself only ever gets single values
+    pub fn zip_lists<F: Fn(Var, Var) -> Result<Var, CalcError> + Copy>(
+            self, other: Self, op: F) -> Result<Self, CalcError> {
+        match (self, other) {
+            (Var::List(ls), Var::List(other_ls)) => {
+                // Both are lists, so do a matrix multiplication
+                let mut new_ls = Vec::new();
+                for var in ls {
+                    let mut folded = var;
+                    for other_var in other_ls.iter() {
+                        folded = folded.zip_lists(other_var.clone(), op)?;
+                    }
+                    new_ls.push(folded);
+                }
+                Ok(Var::List(new_ls))
+            }, (Var::List(ls), other) => {
+                // One is list, so do the operation to with other to every item
+                let new_ls = ls.into_iter()
+                    .map(|var| var.zip_lists(other.clone(), op))
+                    .collect::<Result<Vec<Var>, CalcError>>()?;
+                Ok(Var::List(new_ls))
+            }, (var, Var::List(other_ls)) => {
+                let new_ls = other_ls.into_iter()
+                    .map(|other_var| var.clone().zip_lists(other_var, op))
+                    .collect::<Result<Vec<Var>, CalcError>>()?;
+                Ok(Var::List(new_ls))
+            }, (a, b) => op(a, b)
+        }
+    }
+
+    // Only for single values, i.e. inside do_op once lists are dealt with
+    fn to_fcomplex(&self) -> FComplex {
+        match self {
+            Var::Int(int) => int.to_fcomplex(),
+            Var::Rational(ratio) => FComplex::new_cardinal(ratio_to_f64(ratio), 0.0),
+            Var::Float(num) => *num,
+            Var::Decimal(num) => FComplex::new_cardinal(num.to_f64(), 0.0),
+            Var::List(_) => unreachable!("Lists can't be turned into a single number")
+        }
+    }
+}
+
+pub fn ratio_to_f64(ratio: &BigRational) -> f64 {
+    ratio.to_f64().unwrap_or(f64::NAN)
+}
+
+fn shift_amount(n: &Int) -> Result<u32, CalcError> {
+    match n.to_i64().and_then(|n| u32::try_from(n).ok()) {
+        None if n.is_negative() => Err(CalcError::Domain(format!("Can't shift by a negative amount ({})", n))),
+        None => Err(CalcError::Domain(format!("Shift of {} is too large", n))),
+        Some(n) => Ok(n)
+    }
+}
+
+// Equality works for everything, but ordering only makes sense for real numbers
+fn cmp_holds<T: PartialOrd>(a: T, b: T, op: &str) -> Result<bool, CalcError> {
+    match op {
+        "=" => Ok(a == b),
+        "=/=" => Ok(a != b),
+        _ => match a.partial_cmp(&b) {
+            None => Err(CalcError::Domain(format!("Can't compare complex numbers with '{}'", op))),
+            Some(ord) => Ok(match op {
+                ">" => ord == Ordering::Greater,
+                "<" => ord == Ordering::Less,
+                ">=" => ord != Ordering::Less,
+                "<=" => ord != Ordering::Greater,
+                _ => unreachable!("Unknown comparison operator '{}'", op)
+            })
         }
     }
 }
 
 impl Add for Var {
-    type Output = Self;
-    fn add(self, other: Self) -> Self {
-        self.do_op(other, |a, b| a + b, |a, b| a + b)
+    type Output = Result<Self, CalcError>;
+    fn add(self, other: Self) -> Self::Output {
+        self.do_op(
+            other,
+            |a, b| Ok(a + b), |a, b| Ok(Var::Int(a + b)),
+            |a, b| Ok(Var::from_ratio(a + b)), |a, b| Ok(Var::Decimal(a + b))
+        )
     }
 }
 
 impl Sub for Var {
-    type Output = Self;
-    fn sub(self, other: Self) -> Self {
-        self.do_op(other, |a, b| a - b, |a, b| a - b)
+    type Output = Result<Self, CalcError>;
+    fn sub(self, other: Self) -> Self::Output {
+        self.do_op(
+            other,
+            |a, b| Ok(a - b), |a, b| Ok(Var::Int(a - b)),
+            |a, b| Ok(Var::from_ratio(a - b)), |a, b| Ok(Var::Decimal(a - b))
+        )
     }
 }
 
 impl Mul for Var {
-    type Output = Self;
-    fn mul(self, other: Self) -> Self {
-        self.do_op(other, |a, b| a * b, |a, b| a * b) 
+    type Output = Result<Self, CalcError>;
+    fn mul(self, other: Self) -> Self::Output {
+        self.do_op(
+            other,
+            |a, b| Ok(a * b), |a, b| Ok(Var::Int(a * b)),
+            |a, b| Ok(Var::from_ratio(a * b)), |a, b| Ok(Var::Decimal((a * b)?))
+        )
     }
 }
 
+// Ints that don't divide evenly become fractions (or floats if they're complex)
+// Use the 'div' builtin for floor division instead
 impl Div for Var {
-    type Output = Self;
-    fn div(self, other: Self) -> Self {
-        self.do_op(other, |a, b| a / b, |a, b| a / b)
+    type Output = Result<Self, CalcError>;
+    fn div(self, other: Self) -> Self::Output {
+        self.do_op(
+            other,
+            |a, b| if b == FComplex::zero() { Err(CalcError::DivisionByZero) } else { Ok(a / b) },
+            |a, b| if b == IComplex::zero() {
+                Err(CalcError::DivisionByZero)
+            } else if a.is_real() && b.is_real() {
+                Ok(Var::from_ratio(BigRational::new(a.re.to_big(), b.re.to_big())))
+            } else if (a.clone() % b.clone()) == IComplex::zero() {
+                Ok(Var::Int(a / b))
+            } else {
+                Ok(Var::Float(a.to_fcomplex() / b.to_fcomplex()))
+            }, |a, b| if b.is_zero() {
+                Err(CalcError::DivisionByZero)
+            } else {
+                Ok(Var::from_ratio(a / b))
+            }, |a, b| if b.is_zero() {
+                Err(CalcError::DivisionByZero)
+            } else {
+                Ok(Var::Decimal((a / b)?))
+            }
+        )
     }
 }
 
 // NOTE: Not xor but power
 impl BitXor for Var {
-    type Output = Self;
-    fn bitxor(self, other: Self) -> Self {
-        self.do_op(other, |a, b| a ^ b, |a, b| a ^ b)
+    type Output = Result<Self, CalcError>;
+    fn bitxor(self, other: Self) -> Self::Output {
+        self.do_op(other, float_pow, |a, b| {
+            // Whole, non-negative powers stay exact, and real negative ones become fractions
+            if b.is_real() && !b.re.is_negative() {
+                match b.re.to_i64() {
+                    None => Err(CalcError::Domain(format!("Exponent {} is too large", b.re))),
+                    Some(n) => {
+                        if !a.is_unit_or_zero() {
+                            check_result_bits(approx_log2(&a.norm()) / 2.0, n as u64)?;
+                        }
+                        Ok(Var::Int(a.pow(n as u64)))
+                    }
+                }
+            } else if a.is_real() && b.is_real() {
+                ratio_pow(BigRational::from_integer(a.re.to_big()), &b.re)
+            } else {
+                float_pow(a.to_fcomplex(), b.to_fcomplex()).map(Var::Float)
+            }
+        }, |a, b| if b.is_integer() {
+            ratio_pow(a, &Int::from_big(b.to_integer()))
+        } else {
+            float_pow(
+                FComplex::new_cardinal(ratio_to_f64(&a), 0.0), FComplex::new_cardinal(ratio_to_f64(&b), 0.0)
+            ).map(Var::Float)
+        }, |a, b| if a.is_zero() && b.is_negative() {
+            Err(CalcError::DivisionByZero)
+        } else {
+            // Negative bases to fractional powers are complex, which decimals can't hold
+            Ok(match a.pow(&b)? {
+                Some(res) => Var::Decimal(res),
+                None => Var::Float(
+                    FComplex::new_cardinal(a.to_f64(), 0.0) ^ FComplex::new_cardinal(b.to_f64(), 0.0)
+                )
+            })
+        })
+    }
+}
+
+// 0 to a negative power is 1/0, so it's the same error as dividing by zero
+fn float_pow(a: FComplex, b: FComplex) -> Result<FComplex, CalcError> {
+    if a.len() == 0.0 && b.re < 0.0 {
+        Err(CalcError::DivisionByZero)
+    } else {
+        Ok(a ^ b)
+    }
+}
+
+// log2|x|, close enough for sizing things up
+fn approx_log2(int: &Int) -> f64 {
+    let val = int.to_f64().abs();
+    if val.is_finite() {
+        val.log2()
+    } else {
+        int.bits() as f64
+    }
+}
+
+// Exact fraction to an integer power
+fn ratio_pow(base: BigRational, exp: &Int) -> Result<Var, CalcError> {
+    match exp.to_i64().and_then(|n| i32::try_from(n).ok()) {
+        None => Err(CalcError::Domain(format!("Exponent {} is too large", exp))),
+        Some(n) if n < 0 && base.is_zero() => Err(CalcError::DivisionByZero),
+        Some(n) => {
+            if !(base.is_integer() && base.numer().bits() <= 1) {
+                let bits = approx_log2(&Int::from_big(base.numer().clone()))
+                    + approx_log2(&Int::from_big(base.denom().clone()));
+                check_result_bits(bits, n.unsigned_abs() as u64)?;
+            }
+            Ok(Var::from_ratio(base.pow(n)))
+        }
+    }
+}
+
+// An exact power grows by about log2 of its base every step, so check that up front
+fn check_result_bits(bits: f64, n: u64) -> Result<(), CalcError> {
+    if bits * n as f64 > MAX_RESULT_BITS as f64 {
+        Err(CalcError::Domain(format!(
+            "Exponent {} is too large, the result would be over {} bits", n, MAX_RESULT_BITS
+        )))
+    } else {
+        Ok(())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use num_rational::BigRational;
+    use super::Var;
+    use crate::{
+        int::Int,
+        settings::Settings,
+        decimal::Decimal,
+        complex::{
+            FComplex, IComplex
+        }
+    };
+
+    fn int(re: i64, im: i64) -> Var {
+        Var::Int(IComplex::new_cardinal(Int::from(re), Int::from(im)))
+    }
+
+    fn ratio(num: i64, den: i64) -> Var {
+        Var::from_ratio(BigRational::new(num.into(), den.into()))
+    }
+
+    fn show(res: Result<Var, crate::error::CalcError>) -> String {
+        match res {
+            Ok(val) => val.to_string(&Settings::new()),
+            Err(err) => format!("Error: {}", err)
+        }
+    }
+
+    #[test]
+    fn huge_powers() {
+        let too_large = |n: &str| format!(
+            "Error: Domain error: Exponent {} is too large, the result would be over 1048576 bits", n
+        );
+        assert_eq!(show(int(2, 0) ^ int(10_000_000_000, 0)), too_large("10000000000"));
+        assert_eq!(show(int(1, 1) ^ int(100_000_000_000, 0)), too_large("100000000000"));
+        let three_halves = Var::Rational(BigRational::new(3.into(), 2.into()));
+        assert_eq!(show(three_halves ^ int(2_000_000_000, 0)), too_large("2000000000"));
+        assert_eq!(show(int(2, 0) ^ int(100_000, 0)).len(), 30103);
+
+        // Ones that never grow are fine however big the power is
+        assert_eq!(show(int(0, 0) ^ int(100_000_000_000, 0)), "0");
+        assert_eq!(show(int(1, 0) ^ int(100_000_000_000, 0)), "1");
+        assert_eq!(show(int(-1, 0) ^ int(100_000_000_001, 0)), "-1");
+        assert_eq!(show(int(0, 1) ^ int(100_000_000_003, 0)), "-j1");
+        assert_eq!(show(int(0, -1) ^ int(100_000_000_002, 0)), "-1");
+    }
+
+    #[test]
+    fn bitwise() {
+        let bits = |a: i64, op: &str, b: i64| show(int(a, 0).do_bitwise(int(b, 0), op));
+        assert_eq!(bits(12, "&", 10), "8");
+        assert_eq!(bits(12, "|", 3), "15");
+        assert_eq!(bits(15, "xor", 1), "14");
+        assert_eq!(bits(-12, "&", 10), "0");
+        assert_eq!(show(int(5, 0).to_not()), "-6");
+        assert_eq!(show(int(-1, 0).to_not()), "0");
+
+        let huge = (int(1, 0).do_bitwise(int(70, 0), "<<")).unwrap();
+        assert_eq!(show(huge.do_bitwise(int(68, 0), ">>")), "4");
+        assert_eq!(bits(-16, ">>", 2), "-4");
+        assert_eq!(bits(-16, ">>", 4_000_000_000_000), "-1");
+        assert_eq!(bits(16, ">>", 4_000_000_000_000), "0");
+        let list = Var::List(vec![ int(1, 0), int(2, 0), int(3, 0) ]);
+        assert_eq!(show(list.do_bitwise(int(1, 0), "<<")), "[ 2 4 6 ]");
+
+        let float = Var::Float(FComplex::new_cardinal(1.0, 0.0));
+        assert_eq!(show(float.do_bitwise(int(1, 0), "&")), "Error: Type mismatch: '&' only works on ints");
+        assert_eq!(show(int(1, 1).do_bitwise(int(1, 0), "|")), "Error: Type mismatch: '|' only works on real ints");
+        let half = Var::Rational(BigRational::new(1.into(), 2.into()));
+        assert_eq!(show(half.to_not()), "Error: Type mismatch: '~' only works on ints");
+        assert_eq!(bits(1, "<<", -1), "Error: Domain error: Can't shift by a negative amount (-1)");
+        assert_eq!(
+            bits(1, "<<", 4_294_967_295),
+            "Error: Domain error: Shift of 4294967295 is too large, the result would be over 1048576 bits"
+        );
+        assert_eq!(bits(0, "<<", 4_294_967_295), "0");
+    }
+
+    #[test]
+    fn fractions() {
+        // Always in lowest terms, and back to an int once the denominator's gone
+        assert_eq!(show(int(6, 0) / int(4, 0)), "3/2");
+        assert_eq!(show(int(-6, 0) / int(4, 0)), "-3/2");
+        assert_eq!(show(int(6, 0) / int(-4, 0)), "-3/2");
+        assert_eq!(show(int(6, 0) / int(3, 0)), "2");
+        assert_eq!(show(ratio(1, 2) + ratio(1, 2)), "1");
+        assert_eq!(show(ratio(1, 2) - ratio(1, 3)), "1/6");
+        assert_eq!(show(ratio(2, 3) * int(3, 0)), "2");
+        assert_eq!(show(ratio(2, 3) / ratio(4, 9)), "3/2");
+        assert_eq!(show(ratio(1, 2) / int(0, 0)), "Error: Division by zero");
+
+        // Int powers stay exact, negative ones flip it over
+        assert_eq!(show(ratio(2, 3) ^ int(2, 0)), "4/9");
+        assert_eq!(show(ratio(2, 3) ^ int(-2, 0)), "9/4");
+        assert_eq!(show(int(2, 0) ^ int(-3, 0)), "1/8");
+        assert_eq!(show(ratio(-1, 2) ^ int(-3, 0)), "-8");
+        assert_eq!(show(int(0, 0) ^ int(-1, 0)), "Error: Division by zero");
+        assert_eq!(show(ratio(1, 4) ^ ratio(1, 2)), "0.5");
+        assert_eq!(show(ratio(0, 1) ^ ratio(-1, 2)), "Error: Division by zero");
+
+        // Floats win, and decimals take them in exactly
+        let float = Var::Float(FComplex::new_cardinal(0.5, 0.0));
+        assert_eq!(show(ratio(1, 4) + float), "0.75");
+        let third = Var::Decimal(Decimal::parse("0.5", 30).unwrap()) - ratio(1, 3);
+        assert_eq!(show(third), "0.166666666666666666666666666667");
+        assert_eq!(show(ratio(1, 2) * Var::Decimal(Decimal::parse("3", 30).unwrap())), "1.5");
+        assert_eq!(show(ratio(1, 2) + int(0, 1)), "0.5 + j1");
+    }
+
+    #[test]
+    fn fraction_comparisons() {
+        let cmp = |a: Var, op: &str, b: Var| show(a.do_cmp(b, op));
+        assert_eq!(cmp(ratio(1, 3), "<", ratio(1, 2)), "-1");
+        assert_eq!(cmp(ratio(2, 4), "=", ratio(1, 2)), "-1");
+        assert_eq!(cmp(ratio(-1, 2), ">", ratio(-2, 3)), "-1");
+        assert_eq!(cmp(ratio(3, 2), "<=", int(1, 0)), "0");
+        assert_eq!(cmp(ratio(1, 3), "=/=", Var::Float(FComplex::new_cardinal(1.0 / 3.0, 0.0))), "0");
+        assert_eq!(cmp(ratio(1, 3), ">", Var::Decimal(Decimal::parse("0.333", 30).unwrap())), "-1");
+    }
+
+    // Anything that ends up as a float still can't take 0 to a negative power
+    #[test]
+    fn zero_powers() {
+        let float = |re: f64, im: f64| Var::Float(FComplex::new_cardinal(re, im));
+        assert_eq!(show(float(0.0, 0.0) ^ float(-1.0, 0.0)), "Error: Division by zero");
+        assert_eq!(show(float(0.0, 0.0) ^ float(-0.5, 2.0)), "Error: Division by zero");
+        assert_eq!(show(int(0, 0) ^ float(-1.5, 0.0)), "Error: Division by zero");
+        assert_eq!(show(int(0, 0) ^ int(-1, 1)), "Error: Division by zero");
+        assert_eq!(show(float(0.0, 0.0) ^ float(2.0, 0.0)), "0");
+        assert_eq!(show(float(0.0, 0.0) ^ float(0.0, 0.0)), "1");
+        assert_eq!(show(float(2.0, 0.0) ^ float(-1.0, 0.0)), "0.5");
+    }
+}
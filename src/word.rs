@@ -0,0 +1,341 @@
+/*
+ * Author: Dylan Turner
+ * Description:
+ * - Fixed width ints for programmer mode, e.g. u8 or i32, that wrap around like the hardware does
+ * - Every op works out whether it carried (too big or small unsigned) or overflowed (signed),
+ *   so those can be shown along with the result
+ */
+
+use std::{
+    cell::Cell,
+    fmt::{
+        Display, Formatter, Result as FmtResult
+    }
+};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::One;
+use crate::{
+    var::Var,
+    int::Int,
+    complex::IComplex,
+    error::CalcError
+};
+
+pub const MAX_BITS: u32 = 4096;
+
+// An int type like u8 or i16
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Word {
+    pub bits: u32,
+    pub signed: bool
+}
+
+// Whether anything so far went past the edge of the word, the same as a CPU's flags
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Flags {
+    pub carry: bool, // The unsigned result didn't fit, including borrowing when subtracting
+    pub overflow: bool // The signed result didn't fit
+}
+
+impl Word {
+    pub fn new(bits: u32, signed: bool) -> Option<Self> {
+        (1..=MAX_BITS).contains(&bits).then_some(Self { bits, signed })
+    }
+
+    // u8, i16, etc. Any number of bits works, not just the usual sizes
+    pub fn parse(name: &str) -> Option<Self> {
+        let signed = match name.get(..1)? {
+            "u" => false,
+            "i" => true,
+            _ => return None
+        };
+        Self::new(name.split_at(1).1.parse::<u32>().ok()?, signed)
+    }
+
+    // Keep just the low bits, read as signed if the word is
+    pub fn wrap(&self, int: &Int) -> Int {
+        let low = self.bits_of(int);
+        if self.signed && low.bit(self.bits as u64 - 1) {
+            Int::from_big(low - (BigInt::one() << self.bits))
+        } else {
+            Int::from_big(low)
+        }
+    }
+
+    // The low bits as an unsigned number, i.e. what's actually in the register
+    pub fn bits_of(&self, int: &Int) -> BigInt {
+        int.to_big().mod_floor(&(BigInt::one() << self.bits))
+    }
+
+    // How many digits it takes to write every bit in a base, e.g. 2 for a u8 in hex
+    pub fn width(&self, base: u32) -> usize {
+        ((BigInt::one() << self.bits) - BigInt::one()).to_str_radix(base).len()
+    }
+
+    fn holds(&self, int: &Int) -> bool {
+        self.wrap(int) == *int
+    }
+}
+
+impl Display for Word {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}{}", if self.signed { "i" } else { "u" }, self.bits)
+    }
+}
+
+impl Flags {
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            carry: self.carry || other.carry,
+            overflow: self.overflow || other.overflow
+        }
+    }
+
+    // e.g. [ "carry" ], or nothing if neither is set
+    pub fn names(&self) -> Vec<&str> {
+        [ ("carry", self.carry), ("overflow", self.overflow) ].iter()
+            .filter(|(_, set)| *set)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
+
+// Do an op the way the hardware would. Both sides are read as the unsigned and the signed versions
+// of their bits, and whichever result doesn't fit sets the carry or overflow flag
+// The right side of '<<', '>>' and '^' is a count, so it's used as is
+// Anything besides two real ints, or a result that isn't one (2_^-1_ or 7_/2_), is the usual op
+pub fn word_op<F: Fn(Var, Var) -> Result<Var, CalcError> + Copy>(
+        a: Var, op: &str, b: Var, word: Word, flags: &Cell<Flags>, std_op: F) -> Result<Var, CalcError> {
+    a.zip_lists(b, |a, b| {
+        let (x, y) = match (&a, &b) {
+            (Var::Int(x), Var::Int(y)) if x.is_real() && y.is_real() => (&x.re, &y.re),
+            _ => return std_op(a, b)
+        };
+        let run = |kind: Word| {
+            let x = kind.wrap(x);
+            let y = if matches!(op, "<<" | ">>" | "^") { y.clone() } else { kind.wrap(y) };
+            let bits = Int::from(kind.bits as i64);
+            match op {
+                // Shifting past the edge comes out the same however far it goes, so stop there
+                "<<" | ">>" if y > bits => std_op(int_var(x), int_var(bits)),
+                "^" if past_pow(&x, &y, kind) => Ok(int_var(wrapped_pow(&x, &y, kind))),
+                _ => std_op(int_var(x), int_var(y))
+            }
+        };
+        let unsigned = Word { signed: false, ..word };
+        let signed = Word { signed: true, ..word };
+        match (run(unsigned)?, run(signed)?) {
+            (Var::Int(u), Var::Int(s)) if u.is_real() && s.is_real() => {
+                flags.set(flags.get().merge(Flags {
+                    carry: !unsigned.holds(&u.re),
+                    overflow: !signed.holds(&s.re)
+                }));
+                Ok(int_var(word.wrap(if word.signed { &s.re } else { &u.re })))
+            }, (u, s) => match if word.signed { s } else { u } {
+                // Only one kind came out as an int, like -128 / -1 as an i8, which is 128/255 as a u8
+                Var::Int(int) if int.is_real() => {
+                    flags.set(flags.get().merge(past_edge(word, &int.re)));
+                    Ok(int_var(word.wrap(&int.re)))
+                }, res => Ok(res)
+            }
+        }
+    })
+}
+
+// -x only sets the flag for the kind of word it is, so typing -5_ as an i8 doesn't count as a borrow
+// Literals aren't cut down to size until they're used, so -128_ is fine as an i8 too
+pub fn word_neg(var: Var, word: Word, flags: &Cell<Flags>) -> Var {
+    match var {
+        Var::Int(int) if int.is_real() => {
+            let neg = -int.re;
+            flags.set(flags.get().merge(past_edge(word, &neg)));
+            int_var(word.wrap(&neg))
+        }, Var::List(ls) => Var::List(ls.into_iter().map(|item| word_neg(item, word, flags)).collect()),
+        var => var.to_neg()
+    }
+}
+
+// Fit every real int into the word, e.g. for results of builtins. Doesn't count as carrying
+pub fn wrap_var(var: Var, word: Word) -> Var {
+    match var {
+        Var::Int(int) if int.is_real() => int_var(word.wrap(&int.re)),
+        Var::List(ls) => Var::List(ls.into_iter().map(|item| wrap_var(item, word)).collect()),
+        var => var
+    }
+}
+
+// Whether x^y is sure to be past the edge, i.e. |x|^y is at least 2^bits, which it can't hold either way
+fn past_pow(x: &Int, y: &Int, word: Word) -> bool {
+    x.bits() >= 2 && !y.is_negative() && y.to_big() * (x.bits() - 1) >= BigInt::from(word.bits)
+}
+
+// The low bits of x^y without working out all of it. It's past the edge, so 2^bits is added on to
+// keep it that way, which doesn't change the low bits
+fn wrapped_pow(x: &Int, y: &Int, word: Word) -> Int {
+    let edge = BigInt::one() << word.bits;
+    Int::from_big(word.bits_of(x).modpow(&y.to_big(), &edge) + edge)
+}
+
+// Just the flag for this kind of word, set if the int doesn't fit in it
+fn past_edge(word: Word, int: &Int) -> Flags {
+    let past = !word.holds(int);
+    Flags {
+        carry: past && !word.signed,
+        overflow: past && word.signed
+    }
+}
+
+fn int_var(int: Int) -> Var {
+    Var::Int(IComplex::new_cardinal(int, Int::from(0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use super::{
+        Word, Flags, word_op, word_neg, wrap_var
+    };
+    use crate::{
+        var::Var,
+        int::Int,
+        complex::{
+            FComplex, IComplex
+        }, error::CalcError,
+        settings::Settings
+    };
+
+    fn int(n: i64) -> Var {
+        Var::Int(IComplex::new_cardinal(Int::from(n), Int::from(0)))
+    }
+
+    fn flags(carry: bool, overflow: bool) -> Flags {
+        Flags { carry, overflow }
+    }
+
+    // Run a op b as the word, giving back the result and which flags it set
+    fn run(word: &str, a: Var, op: &str, b: Var) -> (String, Flags) {
+        let std_op = match op {
+            "+" => |a: Var, b: Var| a + b,
+            "-" => |a: Var, b: Var| a - b,
+            "*" => |a: Var, b: Var| a * b,
+            "/" => |a: Var, b: Var| a / b,
+            "^" => |a: Var, b: Var| a ^ b,
+            "<<" => |a: Var, b: Var| a.do_bitwise(b, "<<"),
+            ">>" => |a: Var, b: Var| a.do_bitwise(b, ">>"),
+            _ => |a: Var, b: Var| a.do_bitwise(b, "&")
+        };
+        let set = Cell::new(Flags::default());
+        let res: Result<Var, CalcError> = word_op(a, op, b, Word::parse(word).unwrap(), &set, std_op);
+        (res.unwrap().to_string(&Settings::new()), set.get())
+    }
+
+    fn ints(word: &str, a: i64, op: &str, b: i64) -> (String, Flags) {
+        run(word, int(a), op, int(b))
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(Word::parse("u8"), Some(Word { bits: 8, signed: false }));
+        assert_eq!(Word::parse("i4096"), Some(Word { bits: 4096, signed: true }));
+        for bad in [ "u0", "i4097", "x8", "u", "", "u-8", "i8x" ] {
+            assert_eq!(Word::parse(bad), None, "{} shouldn't be a word", bad);
+        }
+        assert_eq!(Word::parse("i13").unwrap().to_string(), "i13");
+    }
+
+    #[test]
+    fn wrapping() {
+        let wrap = |word: &str, n: i64| Word::parse(word).unwrap().wrap(&Int::from(n));
+        assert_eq!(wrap("u8", 256), Int::from(0));
+        assert_eq!(wrap("u8", -1), Int::from(255));
+        assert_eq!(wrap("i8", 128), Int::from(-128));
+        assert_eq!(wrap("i8", -129), Int::from(127));
+        assert_eq!(wrap("i1", 1), Int::from(-1));
+        assert_eq!(Word::parse("i16").unwrap().width(16), 4);
+        assert_eq!(Word::parse("u12").unwrap().width(8), 4);
+        assert_eq!(Word::parse("u8").unwrap().width(10), 3);
+    }
+
+    // Which flags get set depends on how the bits read as unsigned and as signed, not on the word
+    #[test]
+    fn carry_and_overflow() {
+        assert_eq!(ints("u8", 200, "+", 100), (String::from("44"), flags(true, false)));
+        assert_eq!(ints("u8", 0, "-", 1), (String::from("255"), flags(true, false)));
+        assert_eq!(ints("i8", 127, "+", 1), (String::from("-128"), flags(false, true)));
+        assert_eq!(ints("i8", -1, "+", 1), (String::from("0"), flags(true, false)));
+        assert_eq!(ints("u8", 0x80, "+", 0x80), (String::from("0"), flags(true, true)));
+        assert_eq!(ints("i8", 100, "-", -100), (String::from("-56"), flags(true, true)));
+        assert_eq!(ints("u8", 16, "*", 16), (String::from("0"), flags(true, true)));
+        assert_eq!(ints("u8", 15, "*", 17), (String::from("255"), flags(false, true)));
+        assert_eq!(ints("i8", 2, "^", 7), (String::from("-128"), flags(false, true)));
+        assert_eq!(ints("u8", 7, "+", 8), (String::from("15"), flags(false, false)));
+    }
+
+    // MIN / -1 is the one division that doesn't fit, even though as unsigned it's just a fraction
+    #[test]
+    fn min_over_minus_one() {
+        assert_eq!(ints("i8", -128, "/", -1), (String::from("-128"), flags(false, true)));
+        assert_eq!(ints("i32", i32::MIN as i64, "/", -1), (i32::MIN.to_string(), flags(false, true)));
+        assert_eq!(ints("i8", -128, "*", -1), (String::from("-128"), flags(true, true)));
+        assert_eq!(ints("i8", -128, "/", 2), (String::from("-64"), flags(false, false)));
+        assert_eq!(ints("u8", 250, "/", 2), (String::from("125"), flags(false, false)));
+        assert_eq!(ints("u8", 7, "/", 2), (String::from("7/2"), flags(false, false)));
+    }
+
+    // The shift amount is never wrapped, so shifting by the width or more empties the word
+    #[test]
+    fn wide_shifts() {
+        assert_eq!(ints("u8", 0x80, "<<", 1), (String::from("0"), flags(true, true)));
+        assert_eq!(ints("u8", 5, "<<", 8), (String::from("0"), flags(true, true)));
+        assert_eq!(ints("u8", 1, "<<", 300), (String::from("0"), flags(true, true)));
+        assert_eq!(ints("i8", 1, "<<", 7), (String::from("-128"), flags(false, true)));
+        assert_eq!(ints("u8", 255, ">>", 8), (String::from("0"), flags(false, false)));
+        assert_eq!(ints("i8", -1, ">>", 100), (String::from("-1"), flags(false, false)));
+        assert_eq!(ints("i8", -128, ">>", 7), (String::from("-1"), flags(false, false)));
+
+        // Counts too big to shift or raise to for real still wrap the same way
+        assert_eq!(ints("u8", 1, "<<", 2000000), (String::from("0"), flags(true, true)));
+        assert_eq!(ints("u8", 0, "<<", 2000000), (String::from("0"), flags(false, false)));
+        assert_eq!(ints("u8", 0x80, ">>", i64::MAX), (String::from("0"), flags(false, false)));
+        assert_eq!(ints("i8", -100, ">>", 2000000), (String::from("-1"), flags(false, false)));
+        assert_eq!(ints("i8", 100, ">>", 2000000), (String::from("0"), flags(false, false)));
+        assert_eq!(ints("u8", 2, "^", 2000000), (String::from("0"), flags(true, true)));
+        assert_eq!(ints("u8", 3, "^", 2000000), (String::from("1"), flags(true, true)));
+        assert_eq!(ints("u8", 3, "^", 2000001), (String::from("3"), flags(true, true)));
+        assert_eq!(ints("i8", -3, "^", 2000001), (String::from("-3"), flags(true, true)));
+        assert_eq!(ints("i8", -2, "^", 7), (String::from("-128"), flags(true, false)));
+        assert_eq!(ints("i8", -1, "^", 2000001), (String::from("-1"), flags(true, false)));
+        assert_eq!(ints("u16", 3, "^", 5 + (1 << 16)), (String::from("243"), flags(true, true)));
+        assert_eq!(ints("u4096", 2, "^", 5000), (String::from("0"), flags(true, true)));
+    }
+
+    #[test]
+    fn not_just_ints() {
+        let float = Var::Float(FComplex::new_cardinal(1.5, 0.0));
+        assert_eq!(run("u8", float, "+", int(1)), (String::from("2.5"), flags(false, false)));
+        let list = Var::List(vec![ int(250), int(10) ]);
+        assert_eq!(run("u8", list, "+", int(10)), (String::from("[ 4 20 ]"), flags(true, false)));
+    }
+
+    // Negating only flags the kind of word it is, so -5 isn't a borrow in an i8 but is in a u8
+    #[test]
+    fn negating() {
+        let neg = |word: &str, n: i64| {
+            let set = Cell::new(Flags::default());
+            let res = word_neg(int(n), Word::parse(word).unwrap(), &set);
+            (res.to_string(&Settings::new()), set.get())
+        };
+        assert_eq!(neg("i8", 5), (String::from("-5"), flags(false, false)));
+        assert_eq!(neg("i8", 128), (String::from("-128"), flags(false, false)));
+        assert_eq!(neg("i8", -128), (String::from("-128"), flags(false, true)));
+        assert_eq!(neg("u8", 5), (String::from("251"), flags(true, false)));
+        assert_eq!(neg("u8", 0), (String::from("0"), flags(false, false)));
+    }
+
+    #[test]
+    fn wrapping_vars() {
+        let wrapped = wrap_var(Var::List(vec![ int(-1), int(256) ]), Word::parse("u8").unwrap());
+        assert_eq!(wrapped.to_string(&Settings::new()), "[ 255 0 ]");
+    }
+}